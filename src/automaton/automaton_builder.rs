@@ -1,13 +1,26 @@
+use crate::automaton::block_rule::BlockRule;
+use crate::automaton::boundary::Boundary;
 use crate::automaton::grid::Grid;
-use crate::automaton::rules::{Rule, Rules};
+use crate::automaton::palette::Palette;
+use crate::automaton::parsers::rle::RlePattern;
+use crate::automaton::parsers::schemas::{IncludeSchema, ScenarioSchema};
+use crate::automaton::rule_map::RuleMap;
+use crate::automaton::rules::{ElementaryRule, Rule, Rules, DEFAULT_STATES};
+use crate::automaton::schedule::Schedule;
 use crate::automaton::Automaton;
-use exitfailure::ExitFailure;
+use crate::error::Error;
 use ndarray::{ArrayD, IxDyn};
-use std::fmt;
+use std::convert::TryFrom;
 
 pub struct AutomatonBuilder {
     grid: Grid,
     rules: Rules,
+    rule_map: Option<RuleMap>,
+    block_rule: Option<BlockRule>,
+    palette: Palette,
+    second_order: bool,
+    seed: u64,
+    schedule: Schedule,
 }
 
 impl AutomatonBuilder {
@@ -16,35 +29,179 @@ impl AutomatonBuilder {
         AutomatonBuilder {
             grid: Grid::new(dims.clone(), ArrayD::zeros(IxDyn(&dims[..]))),
             rules: Rules::new(Vec::new()),
+            rule_map: None,
+            block_rule: None,
+            palette: Palette::default(),
+            second_order: false,
+            seed: 0,
+            schedule: Schedule::default(),
         }
     }
 
+    /// builder for the automaton a resolved scenario describes
+    pub fn from_scenario(scenario: &ScenarioSchema) -> Result<AutomatonBuilder, Error> {
+        let dims = &scenario.dimensions.dimensions;
+        let mut builder = AutomatonBuilder::new(dims.clone());
+        if !scenario.dimensions.boundary.is_empty() {
+            builder.set_boundaries(
+                scenario
+                    .dimensions
+                    .boundary
+                    .iter()
+                    .map(Boundary::from)
+                    .collect(),
+            )?;
+        }
+        for coordinate in &scenario.coordinates {
+            builder.set_point(coordinate)?;
+        }
+        if scenario.rules.is_some() && scenario.rule_map.is_some() {
+            return Err(Error::Build(
+                "a scenario gives either rules or a rule map, not both".to_string(),
+            ));
+        }
+        let mut rules_schema = match &scenario.rules {
+            Some(rules) => Some(inline(rules)?),
+            None => None,
+        };
+        if let Some(rule_map) = &scenario.rule_map {
+            let rule_map = inline(rule_map)?;
+            builder.set_rule_map(RuleMap::from_schema(rule_map, dims)?);
+            // the palette and schedule of the first rule set apply to all
+            rules_schema = rule_map.rules.first();
+        } else if let Some(schema) = rules_schema {
+            builder.set_rules(Rules::try_from(schema)?);
+        }
+        if let Some(schema) = rules_schema {
+            if let Some(block_rule) = &schema.block_rule {
+                builder.set_block_rule(BlockRule::from_schema(
+                    block_rule,
+                    schema.states.unwrap_or(DEFAULT_STATES),
+                    dims.len(),
+                )?);
+            }
+            if let Some(schedule) = &schema.schedule {
+                builder.set_schedule(Schedule::from_schema(schedule, dims)?);
+            }
+            if let Some(colors) = &schema.palette {
+                builder.set_palette(Palette::new(colors.clone()));
+            }
+        }
+        if let Some(seed) = scenario.seed {
+            builder.set_seed(seed);
+        }
+        Ok(builder)
+    }
+
     /// generate a new Automaton
-    pub fn build(&mut self) -> Result<Automaton, ExitFailure> {
-        if self.rules.is_empty() {
-            return Err(ExitFailure::from(BuildError::new(
-                "cannot build an automaton with no rules",
-            )));
+    pub fn build(&mut self) -> Result<Automaton, Error> {
+        let all_rules = match &self.rule_map {
+            Some(map) => map.rules().iter().collect(),
+            None => vec![&self.rules],
+        };
+        if self.block_rule.is_some() {
+            if self.rule_map.is_some() || !self.rules.is_empty() {
+                return Err(Error::Build(
+                    "a block rule replaces the rules of every cell".to_string(),
+                ));
+            }
+        } else {
+            for rules in &all_rules {
+                if rules.is_empty() {
+                    return Err(Error::EmptyRules);
+                }
+                rules.validate()?;
+                rules.check_shape(self.grid.dims().len())?;
+            }
+        }
+        if self.second_order && self.schedule != Schedule::Synchronous {
+            return Err(Error::SecondOrderSchedule);
+        }
+        let states = match &self.block_rule {
+            Some(rule) => rule.states(),
+            None => all_rules[0].states(),
+        };
+        if let Some((point, value)) = self.grid.iter().find(|(_, value)| *value >= states) {
+            let cause = format!(
+                "point {:?} has value {} but only {} states are declared",
+                point, value, states
+            );
+            return Err(Error::Build(cause));
+        }
+        let mut grid = self.grid.clone();
+        if states == 2 {
+            grid.pack()?;
+        }
+        let mut automaton = Automaton::new(grid, all_rules[0].clone());
+        if let Some(map) = &self.rule_map {
+            automaton.set_rule_map(map.clone())?;
         }
-        Ok(Automaton::new(self.grid.clone(), self.rules.clone()))
+        if let Some(rule) = &self.block_rule {
+            automaton.set_block_rule(rule.clone())?;
+        }
+        automaton.set_palette(self.palette.clone());
+        automaton.set_seed(self.seed);
+        automaton.set_schedule(self.schedule.clone())?;
+        automaton.set_second_order(self.second_order)?;
+        Ok(automaton)
     }
 
     /// add a field to the encryption data
-    pub fn set_point<'a>(&'a mut self, point: &[usize]) -> Result<&'a mut Self, ExitFailure> {
+    pub fn set_point<'a>(&'a mut self, point: &[usize]) -> Result<&'a mut Self, Error> {
         self.grid.set_point(point, 1)?;
         Ok(self)
     }
 
+    /// set a point to a given state
+    pub fn set_point_value(&mut self, point: &[usize], value: u32) -> Result<&mut Self, Error> {
+        self.grid.set_point(point, value)?;
+        Ok(self)
+    }
+
+    /// set the cells of a pattern at an offset
+    pub fn set_pattern(
+        &mut self,
+        pattern: &RlePattern,
+        offset: &[usize],
+    ) -> Result<&mut Self, Error> {
+        pattern.place(&mut self.grid, offset)?;
+        Ok(self)
+    }
+
     /// set multiple points at once
-    pub fn set_points<'a>(&'a mut self, points: &[&[usize]]) -> Result<&'a mut Self, ExitFailure> {
+    pub fn set_points<'a>(&'a mut self, points: &[&[usize]]) -> Result<&'a mut Self, Error> {
         for point in points {
             self.set_point(point)?;
         }
         Ok(self)
     }
 
+    /// build a second-order automaton, which can be run backwards
+    pub fn set_second_order(&mut self) -> &mut Self {
+        self.second_order = true;
+        self
+    }
+
+    /// seed the random values drawn by stochastic rules
+    pub fn set_seed(&mut self, seed: u64) -> &mut Self {
+        self.seed = seed;
+        self
+    }
+
+    /// set the order in which the cells of a generation are updated
+    pub fn set_schedule(&mut self, schedule: Schedule) -> &mut Self {
+        self.schedule = schedule;
+        self
+    }
+
+    /// set the boundary of every axis, or of all axes at once
+    pub fn set_boundaries(&mut self, boundaries: Vec<Boundary>) -> Result<&mut Self, Error> {
+        self.grid.set_boundaries(boundaries)?;
+        Ok(self)
+    }
+
     /// add rule
-    pub fn add_rule<'a>(&'a mut self, rule: Box<dyn Rule>) -> &'a mut Self {
+    pub fn add_rule(&mut self, rule: Box<dyn Rule>) -> &mut Self {
         self.rules.add(&mut vec![rule]);
         self
     }
@@ -56,29 +213,46 @@ impl AutomatonBuilder {
     }
 
     /// set rules
-    pub fn set_rules<'a>(&'a mut self, rules: Rules) -> &'a mut AutomatonBuilder {
+    pub fn set_rules(&mut self, rules: Rules) -> &mut AutomatonBuilder {
         self.rules = rules;
         self
     }
-}
 
-#[derive(Debug)]
-struct BuildError {
-    cause: String,
-}
+    /// set a rule per cell
+    pub fn set_rule_map(&mut self, map: RuleMap) -> &mut AutomatonBuilder {
+        self.rule_map = Some(map);
+        self
+    }
 
-impl BuildError {
-    pub fn new(cause: &str) -> BuildError {
-        BuildError {
-            cause: cause.to_string(),
-        }
+    /// set a block rule
+    pub fn set_block_rule(&mut self, rule: BlockRule) -> &mut AutomatonBuilder {
+        self.block_rule = Some(rule);
+        self
     }
-}
 
-impl fmt::Display for BuildError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "build failed! {}", self.cause)
+    /// set the colors used to draw each state
+    pub fn set_palette(&mut self, palette: Palette) -> &mut AutomatonBuilder {
+        self.palette = palette;
+        self
+    }
+
+    /// set rules to a single Wolfram elementary rule
+    pub fn set_elementary_rule(&mut self, number: u8) -> &mut AutomatonBuilder {
+        self.rules = Rules::new(vec![Box::new(ElementaryRule::new(number))]);
+        self
     }
 }
 
-impl std::error::Error for BuildError {}
+/// the part of a scenario given in place
+fn inline<T>(part: &IncludeSchema<T>) -> Result<&T, Error> {
+    match part {
+        IncludeSchema::Inline(part) => Ok(part),
+        IncludeSchema::Include { include } => {
+            let cause = format!(
+                "{} is included but was not read, read scenarios with parse_scenario",
+                include.display()
+            );
+            Err(Error::Build(cause))
+        }
+    }
+}
@@ -0,0 +1,355 @@
+use crate::automaton::boundary::{Boundary, Position};
+use crate::automaton::grid::CellStorage;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+/// binary grid packed 64 cells to a word, each row word aligned
+#[derive(Debug, Clone, PartialEq)]
+pub struct BitGrid {
+    row_len: usize,
+    rows: usize,
+    words_per_row: usize,
+    words: Vec<u64>,
+}
+
+impl BitGrid {
+    /// an empty grid of the given dimensions
+    pub fn new(dims: &[usize]) -> Self {
+        let row_len = dims.last().copied().unwrap_or(1);
+        let rows = dims[..dims.len().saturating_sub(1)].iter().product();
+        let words_per_row = row_len.div_ceil(64);
+        Self {
+            row_len,
+            rows,
+            words_per_row,
+            words: vec![0; rows * words_per_row],
+        }
+    }
+
+    /// number of cells along the last axis
+    pub fn row_len(&self) -> usize {
+        self.row_len
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn words_per_row(&self) -> usize {
+        self.words_per_row
+    }
+
+    pub fn words(&self) -> &[u64] {
+        &self.words[..]
+    }
+
+    pub fn words_mut(&mut self) -> &mut [u64] {
+        &mut self.words[..]
+    }
+
+    pub fn row(&self, row: usize) -> &[u64] {
+        &self.words[row * self.words_per_row..(row + 1) * self.words_per_row]
+    }
+
+    /// bits of word `k` of a row that hold cells
+    fn mask(&self, k: usize) -> u64 {
+        let used = self.row_len - k * 64;
+        if used >= 64 {
+            !0
+        } else {
+            (1 << used) - 1
+        }
+    }
+
+    fn row_bit(&self, row: &[u64], column: usize) -> u64 {
+        (row[column / 64] >> (column % 64)) & 1
+    }
+
+    /// what lies just past the left and right end of a row
+    fn row_edges(&self, row: &[u64], boundary: Boundary) -> (u64, u64) {
+        let edge = |column: i64| match boundary.resolve(column, self.row_len) {
+            Position::Inside(column) => self.row_bit(row, column),
+            Position::Outside(value) => u64::from(value != 0),
+        };
+        (edge(-1), edge(self.row_len as i64))
+    }
+
+    /// the row `delta` rows away from `row` along the first axis
+    fn neighbor_row(&self, row: usize, delta: i64, boundary: Boundary) -> Row<'_> {
+        match boundary.resolve(row as i64 + delta, self.rows) {
+            Position::Inside(row) => Row::Cells(self.row(row)),
+            Position::Outside(value) => Row::Filled(value != 0),
+        }
+    }
+
+    /// left neighbors, cells and right neighbors of word k of a row
+    fn spread(&self, row: &Row<'_>, k: usize, edges: (u64, u64)) -> (u64, u64, u64) {
+        let mask = self.mask(k);
+        let words = match row {
+            Row::Cells(words) => words,
+            Row::Filled(true) => return (mask, mask, mask),
+            Row::Filled(false) => return (0, 0, 0),
+        };
+        let last = self.words_per_row - 1;
+        let cells = words[k];
+        let carry_left = if k == 0 { edges.0 } else { words[k - 1] >> 63 };
+        let mut right = cells >> 1;
+        if k < last {
+            right |= words[k + 1] << 63;
+        } else {
+            right |= edges.1 << ((self.row_len - 1) % 64);
+        }
+        (((cells << 1) | carry_left) & mask, cells, right & mask)
+    }
+}
+
+impl CellStorage for BitGrid {
+    fn len(&self) -> usize {
+        self.rows * self.row_len
+    }
+
+    fn get(&self, index: usize) -> u32 {
+        let row = index / self.row_len;
+        let column = index % self.row_len;
+        self.row_bit(self.row(row), column) as u32
+    }
+
+    fn set(&mut self, index: usize, value: u32) {
+        let row = index / self.row_len;
+        let column = index % self.row_len;
+        let word = &mut self.words[row * self.words_per_row + column / 64];
+        let bit = 1 << (column % 64);
+        if value != 0 {
+            *word |= bit;
+        } else {
+            *word &= !bit;
+        }
+    }
+}
+
+/// words of a single row of a `BitGrid`, written cell by cell
+pub struct BitRow<'a> {
+    words: &'a mut [u64],
+    len: usize,
+}
+
+impl<'a> BitRow<'a> {
+    pub fn new(words: &'a mut [u64], len: usize) -> Self {
+        Self { words, len }
+    }
+}
+
+impl CellStorage for BitRow<'_> {
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn get(&self, index: usize) -> u32 {
+        ((self.words[index / 64] >> (index % 64)) & 1) as u32
+    }
+
+    fn set(&mut self, index: usize, value: u32) {
+        let bit = 1 << (index % 64);
+        if value != 0 {
+            self.words[index / 64] |= bit;
+        } else {
+            self.words[index / 64] &= !bit;
+        }
+    }
+}
+
+/// a row next to the one being stepped
+enum Row<'a> {
+    Cells(&'a [u64]),
+    /// a row past a fixed edge, all 0s or all 1s
+    Filled(bool),
+}
+
+/// step a one-dimensional grid by an elementary rule number
+pub fn step_elementary(current: &BitGrid, next: &mut BitGrid, rule: u8, boundary: Boundary) {
+    if current.words.is_empty() {
+        return;
+    }
+    let row = current.row(0);
+    let edges = current.row_edges(row, boundary);
+    for k in 0..current.words_per_row {
+        let (left, cells, right) = current.spread(&Row::Cells(row), k, edges);
+        next.words[k] = elementary_word(rule, left, cells, right) & current.mask(k);
+    }
+}
+
+/// step a one-dimensional grid with an elementary rule per cell
+pub fn step_hybrid_elementary(
+    current: &BitGrid,
+    next: &mut BitGrid,
+    rules: &[(u8, BitGrid)],
+    boundary: Boundary,
+) {
+    if current.words.is_empty() {
+        return;
+    }
+    let row = current.row(0);
+    let edges = current.row_edges(row, boundary);
+    for k in 0..current.words_per_row {
+        let (left, cells, right) = current.spread(&Row::Cells(row), k, edges);
+        next.words[k] = rules.iter().fold(0, |out, (rule, mask)| {
+            out | (elementary_word(*rule, left, cells, right) & mask.words[k])
+        });
+    }
+}
+
+/// next value of 64 cells by an elementary rule
+fn elementary_word(rule: u8, left: u64, cells: u64, right: u64) -> u64 {
+    let mut out = 0;
+    for pattern in 0..8 {
+        if (rule >> pattern) & 1 == 1 {
+            out |=
+                select(left, pattern & 4) & select(cells, pattern & 2) & select(right, pattern & 1);
+        }
+    }
+    out
+}
+
+/// step a two-dimensional grid by a Life-like rule
+pub fn step_life_like(
+    current: &BitGrid,
+    next: &mut BitGrid,
+    birth: u16,
+    survival: u16,
+    boundaries: &[Boundary],
+) {
+    if current.words.is_empty() {
+        return;
+    }
+    let step_row = |(row, out): (usize, &mut [u64])| {
+        let rows = [
+            current.neighbor_row(row, -1, boundaries[0]),
+            Row::Cells(current.row(row)),
+            current.neighbor_row(row, 1, boundaries[0]),
+        ];
+        let edges = |row: &Row<'_>| match row {
+            Row::Cells(words) => current.row_edges(words, boundaries[1]),
+            Row::Filled(_) => (0, 0),
+        };
+        let edges = [edges(&rows[0]), edges(&rows[1]), edges(&rows[2])];
+        for (k, word) in out.iter_mut().enumerate() {
+            let (up_left, up, up_right) = current.spread(&rows[0], k, edges[0]);
+            let (left, cells, right) = current.spread(&rows[1], k, edges[1]);
+            let (down_left, down, down_right) = current.spread(&rows[2], k, edges[2]);
+            // bit-sliced count of the live neighbors of each of the 64 cells
+            let mut count = [0u64; 4];
+            for neighbors in &[
+                up_left, up, up_right, left, right, down_left, down, down_right,
+            ] {
+                let mut carry = *neighbors;
+                for plane in count.iter_mut() {
+                    let overflow = *plane & carry;
+                    *plane ^= carry;
+                    carry = overflow;
+                }
+            }
+            let mut born = 0;
+            let mut survives = 0;
+            for n in 0..9 {
+                let exact = (0..4).fold(!0, |acc, bit| acc & select(count[bit], n & (1 << bit)));
+                if (birth >> n) & 1 == 1 {
+                    born |= exact;
+                }
+                if (survival >> n) & 1 == 1 {
+                    survives |= exact;
+                }
+            }
+            *word = ((born & !cells) | (survives & cells)) & current.mask(k);
+        }
+    };
+
+    #[cfg(not(feature = "parallel"))]
+    next.words
+        .chunks_mut(current.words_per_row)
+        .enumerate()
+        .for_each(step_row);
+
+    #[cfg(feature = "parallel")]
+    next.words
+        .par_chunks_mut(current.words_per_row)
+        .enumerate()
+        .for_each(step_row);
+}
+
+/// the word itself where `bit` is set, its complement where it is not
+fn select(word: u64, bit: usize) -> u64 {
+    if bit != 0 {
+        word
+    } else {
+        !word
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn from_cells(dims: &[usize], cells: &[u32]) -> BitGrid {
+        let mut grid = BitGrid::new(dims);
+        for (index, value) in cells.iter().enumerate() {
+            grid.set(index, *value);
+        }
+        grid
+    }
+
+    #[test]
+    fn rows_should_start_on_fresh_words() {
+        let mut grid = BitGrid::new(&[3, 70]);
+        assert_eq!(grid.words_per_row(), 2);
+        assert_eq!(grid.words().len(), 6);
+        grid.set(70, 1);
+        grid.set(139, 1);
+        assert_eq!(grid.row(1), &[1, 1 << 5]);
+        assert_eq!(grid.get(70), 1);
+        assert_eq!(grid.get(71), 0);
+        grid.set(70, 0);
+        assert_eq!(grid.row(1), &[0, 1 << 5]);
+    }
+
+    #[test]
+    fn elementary_should_wrap_across_words() {
+        // rule 2: a cell is set when only its right neighbor was
+        let mut cells = vec![0; 130];
+        cells[0] = 1;
+        cells[64] = 1;
+        let current = from_cells(&[130], &cells);
+        let mut next = BitGrid::new(&[130]);
+        step_elementary(&current, &mut next, 2, Boundary::Periodic);
+        let expected = from_cells(&[130], &{
+            let mut cells = vec![0; 130];
+            cells[129] = 1;
+            cells[63] = 1;
+            cells
+        });
+        assert_eq!(next, expected);
+    }
+
+    #[test]
+    fn life_like_should_move_glider() {
+        let mut current = BitGrid::new(&[6, 66]);
+        for index in &[1, 66 + 2, 132, 133, 134] {
+            current.set(*index, 1);
+        }
+        let mut next = BitGrid::new(&[6, 66]);
+        for _ in 0..4 {
+            step_life_like(
+                &current,
+                &mut next,
+                1 << 3,
+                (1 << 2) | (1 << 3),
+                &[Boundary::Periodic; 2],
+            );
+            std::mem::swap(&mut current, &mut next);
+        }
+        let mut expected = BitGrid::new(&[6, 66]);
+        for index in &[66 + 2, 132 + 3, 198 + 1, 198 + 2, 198 + 3] {
+            expected.set(*index, 1);
+        }
+        assert_eq!(current, expected);
+    }
+}
@@ -0,0 +1,290 @@
+use crate::automaton::boundary::Boundary;
+use crate::automaton::compiled_rules::MAX_TABLE_SIZE;
+use crate::automaton::grid::CellStorage;
+use crate::automaton::parsers::schemas::{BlockRuleSchema, BlockTransitionSchema};
+use crate::error::Error;
+
+/// block rule on the Margolus neighborhood: 2-cell blocks, shifted on odd
+/// generations, replaced through a table. Cells of a block are listed with
+/// the first axis changing slowest
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockRule {
+    dimensions: usize,
+    states: u32,
+    /// next block number of every block number, in base states
+    table: Vec<u32>,
+}
+
+impl BlockRule {
+    /// rule leaving every block as it is
+    pub fn new(dimensions: usize, states: u32) -> Result<Self, Error> {
+        let size = (states as usize)
+            .checked_pow(1 << dimensions.min(31))
+            .filter(|size| *size <= MAX_TABLE_SIZE && states >= 2)
+            .ok_or_else(|| {
+                let cause = format!(
+                    "cannot build a block table for {} states in {} dimensions",
+                    states, dimensions
+                );
+                Error::BlockRule(cause)
+            })?;
+        Ok(Self {
+            dimensions,
+            states,
+            table: (0..size as u32).collect(),
+        })
+    }
+
+    pub fn dimensions(&self) -> usize {
+        self.dimensions
+    }
+
+    pub fn states(&self) -> u32 {
+        self.states
+    }
+
+    /// number of cells in a block
+    pub fn block_size(&self) -> usize {
+        1 << self.dimensions
+    }
+
+    /// replace every block holding the cells `from` by `to`
+    pub fn set_transition(&mut self, from: &[u32], to: &[u32]) -> Result<&mut Self, Error> {
+        let from = self.encode(from)?;
+        let to = self.encode(to)?;
+        self.table[from] = to as u32;
+        Ok(self)
+    }
+
+    /// the cells a block is replaced by
+    pub fn apply(&self, block: &[u32]) -> Result<Vec<u32>, Error> {
+        Ok(self.decode(self.table[self.encode(block)?] as usize))
+    }
+
+    /// rule undoing this one, if any
+    pub fn inverse(&self) -> Option<Self> {
+        let mut table = vec![None; self.table.len()];
+        for (from, to) in self.table.iter().enumerate() {
+            if table[*to as usize].replace(from as u32).is_some() {
+                return None;
+            }
+        }
+        Some(Self {
+            dimensions: self.dimensions,
+            states: self.states,
+            table: table.into_iter().map(Option::unwrap).collect(),
+        })
+    }
+
+    /// check the rule fits the grid dimensions and boundaries
+    pub fn check(&self, dims: &[usize], boundaries: &[Boundary]) -> Result<(), Error> {
+        if dims.len() != self.dimensions {
+            return Err(Error::DimensionMismatch {
+                expected: dims.len(),
+                found: self.dimensions,
+            });
+        }
+        for (axis, (dim, boundary)) in dims.iter().zip(boundaries).enumerate() {
+            if *boundary == Boundary::Periodic && dim % 2 == 1 {
+                let cause = format!(
+                    "periodic axis {} of length {} must have an even length to be split into blocks",
+                    axis, dim
+                );
+                return Err(Error::BlockRule(cause));
+            }
+        }
+        Ok(())
+    }
+
+    /// compute the next generation of source into out
+    pub fn step<S, O>(
+        &self,
+        source: &S,
+        out: &mut O,
+        dims: &[usize],
+        boundaries: &[Boundary],
+        odd: bool,
+    ) where
+        S: CellStorage + ?Sized,
+        O: CellStorage + ?Sized,
+    {
+        for index in 0..source.len() {
+            out.set(index, source.get(index));
+        }
+        let start = usize::from(odd);
+        if dims.iter().any(|dim| *dim <= start) {
+            return;
+        }
+        let mut origin = vec![start; dims.len()];
+        let mut cells = vec![0; self.block_size()];
+        loop {
+            if self.block_cells(&origin, dims, boundaries, &mut cells) {
+                let block = cells
+                    .iter()
+                    .fold(0, |number, cell| number * self.states + source.get(*cell));
+                let mut next = self.table[block as usize];
+                for cell in cells.iter().rev() {
+                    out.set(*cell, next % self.states);
+                    next /= self.states;
+                }
+            }
+            // move to the next block, the last axis first
+            let mut axis = dims.len();
+            loop {
+                if axis == 0 {
+                    return;
+                }
+                axis -= 1;
+                origin[axis] += 2;
+                if origin[axis] < dims[axis] {
+                    break;
+                }
+                origin[axis] = start;
+            }
+        }
+    }
+
+    /// build a rule from its config
+    pub fn from_schema(
+        schema: &BlockRuleSchema,
+        states: u32,
+        dimensions: usize,
+    ) -> Result<Self, Error> {
+        let mut rule = BlockRule::new(dimensions, states)?;
+        for transition in &schema.transitions {
+            rule.set_transition(&transition.from, &transition.to)?;
+        }
+        Ok(rule)
+    }
+
+    /// the configuration of the rule, listing every block that changes
+    pub fn to_schema(&self) -> BlockRuleSchema {
+        BlockRuleSchema {
+            transitions: self
+                .table
+                .iter()
+                .enumerate()
+                .filter(|(from, to)| *from != **to as usize)
+                .map(|(from, to)| BlockTransitionSchema {
+                    from: self.decode(from),
+                    to: self.decode(*to as usize),
+                })
+                .collect(),
+        }
+    }
+
+    /// linear indices of the cells of a block, or false past a fixed edge
+    fn block_cells(
+        &self,
+        origin: &[usize],
+        dims: &[usize],
+        boundaries: &[Boundary],
+        cells: &mut [usize],
+    ) -> bool {
+        for (cell, index) in cells.iter_mut().enumerate() {
+            *index = 0;
+            for axis in 0..dims.len() {
+                let mut coordinate = origin[axis] + ((cell >> (dims.len() - 1 - axis)) & 1);
+                if coordinate == dims[axis] {
+                    if boundaries[axis] != Boundary::Periodic {
+                        return false;
+                    }
+                    coordinate = 0;
+                }
+                *index = *index * dims[axis] + coordinate;
+            }
+        }
+        true
+    }
+
+    /// the number of a block
+    fn encode(&self, block: &[u32]) -> Result<usize, Error> {
+        if block.len() != self.block_size() || block.iter().any(|cell| *cell >= self.states) {
+            let cause = format!(
+                "a block should list {} cells with states below {}, got {:?}",
+                self.block_size(),
+                self.states,
+                block
+            );
+            return Err(Error::BlockRule(cause));
+        }
+        Ok(block.iter().fold(0, |number, cell| {
+            number * self.states as usize + *cell as usize
+        }))
+    }
+
+    /// the cells of a block, from its number
+    fn decode(&self, number: usize) -> Vec<u32> {
+        let mut block = vec![0; self.block_size()];
+        let mut rest = number as u32;
+        for cell in block.iter_mut().rev() {
+            *cell = rest % self.states;
+            rest /= self.states;
+        }
+        block
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::automaton::parsers::schemas::RulesSchema;
+    use crate::automaton::test_configs::read_config;
+
+    fn config_rule(path: &str) -> BlockRule {
+        let schema = read_config::<RulesSchema>(path);
+        BlockRule::from_schema(&schema.block_rule.unwrap(), 2, 2).unwrap()
+    }
+
+    #[test]
+    fn blocks_should_alternate_and_wrap() {
+        // every block is rotated a quarter turn clockwise
+        let mut rule = BlockRule::new(2, 2).unwrap();
+        for block in 0..16u32 {
+            let cells: Vec<u32> = (0..4).rev().map(|bit| (block >> bit) & 1).collect();
+            rule.set_transition(&cells, &[cells[2], cells[0], cells[3], cells[1]])
+                .unwrap();
+        }
+        let mut source = vec![0u32; 16];
+        source[0] = 1;
+        let mut out = vec![0u32; 16];
+        let periodic = [Boundary::Periodic; 2];
+        rule.step(&source[..], &mut out[..], &[4, 4], &periodic, false);
+        assert_eq!(out.iter().position(|cell| *cell == 1), Some(1));
+        // on odd generations the block at the origin holds the far corners
+        rule.step(&source[..], &mut out[..], &[4, 4], &periodic, true);
+        assert_eq!(out.iter().position(|cell| *cell == 1), Some(3));
+        // past a fixed edge the block is left alone
+        let fixed = [Boundary::ZeroPadded; 2];
+        rule.step(&source[..], &mut out[..], &[4, 4], &fixed, true);
+        assert_eq!(out, source);
+    }
+
+    #[test]
+    fn check_should_need_even_periodic_axes() {
+        let rule = BlockRule::new(2, 2).unwrap();
+        assert!(rule.check(&[4, 6], &[Boundary::Periodic; 2]).is_ok());
+        assert!(rule.check(&[4, 5], &[Boundary::Periodic; 2]).is_err());
+        assert!(rule
+            .check(&[4, 5], &[Boundary::Periodic, Boundary::Reflecting])
+            .is_ok());
+        assert!(rule.check(&[4], &[Boundary::Periodic]).is_err());
+        assert!(BlockRule::new(5, 2).is_err());
+    }
+
+    #[test]
+    fn critters_should_be_invertible() {
+        let critters = config_rule("critters/rules.json");
+        // two live cells stay, others are complemented, three are turned
+        assert_eq!(critters.apply(&[1, 0, 0, 1]).unwrap(), vec![1, 0, 0, 1]);
+        assert_eq!(critters.apply(&[0, 0, 0, 0]).unwrap(), vec![1, 1, 1, 1]);
+        assert_eq!(critters.apply(&[1, 1, 0, 1]).unwrap(), vec![0, 1, 0, 0]);
+        let inverse = critters.inverse().unwrap();
+        for block in 0..16u32 {
+            let cells: Vec<u32> = (0..4).rev().map(|bit| (block >> bit) & 1).collect();
+            let next = critters.apply(&cells).unwrap();
+            assert_eq!(inverse.apply(&next).unwrap(), cells);
+        }
+        assert!(config_rule("sand/rules.json").inverse().is_none());
+    }
+}
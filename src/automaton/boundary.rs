@@ -0,0 +1,111 @@
+use crate::automaton::parsers::schemas::BoundarySchema;
+
+/// what a neighborhood sees past the edge of the grid along one axis
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Boundary {
+    /// wrap around to the opposite edge, making the grid a torus
+    #[default]
+    Periodic,
+    /// every cell past the edge holds the given value
+    Fixed(u32),
+    /// cells past the edge mirror the cells inside it
+    Reflecting,
+    /// every cell past the edge is 0
+    ZeroPadded,
+}
+
+/// where a coordinate lands after applying a boundary
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Position {
+    Inside(usize),
+    Outside(u32),
+}
+
+impl Boundary {
+    /// map a coordinate back into the grid, or to a boundary value
+    pub fn resolve(&self, coordinate: i64, size: usize) -> Position {
+        let size = size as i64;
+        if coordinate >= 0 && coordinate < size {
+            return Position::Inside(coordinate as usize);
+        }
+        match self {
+            Boundary::Periodic => Position::Inside(coordinate.rem_euclid(size) as usize),
+            Boundary::Fixed(value) => Position::Outside(*value),
+            Boundary::ZeroPadded => Position::Outside(0),
+            Boundary::Reflecting => {
+                let folded = coordinate.rem_euclid(2 * size);
+                if folded < size {
+                    Position::Inside(folded as usize)
+                } else {
+                    Position::Inside((2 * size - 1 - folded) as usize)
+                }
+            }
+        }
+    }
+}
+
+impl From<&BoundarySchema> for Boundary {
+    fn from(schema: &BoundarySchema) -> Self {
+        match schema {
+            BoundarySchema::Periodic => Boundary::Periodic,
+            BoundarySchema::Fixed(value) => Boundary::Fixed(*value),
+            BoundarySchema::Reflecting => Boundary::Reflecting,
+            BoundarySchema::ZeroPadded => Boundary::ZeroPadded,
+        }
+    }
+}
+
+impl From<&Boundary> for BoundarySchema {
+    fn from(boundary: &Boundary) -> Self {
+        match boundary {
+            Boundary::Periodic => BoundarySchema::Periodic,
+            Boundary::Fixed(value) => BoundarySchema::Fixed(*value),
+            Boundary::Reflecting => BoundarySchema::Reflecting,
+            Boundary::ZeroPadded => BoundarySchema::ZeroPadded,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::automaton::automaton_builder::AutomatonBuilder;
+
+    #[test]
+    fn periodic_should_wrap() {
+        assert_eq!(Boundary::Periodic.resolve(-1, 5), Position::Inside(4));
+        assert_eq!(Boundary::Periodic.resolve(5, 5), Position::Inside(0));
+        assert_eq!(Boundary::Periodic.resolve(-7, 5), Position::Inside(3));
+    }
+
+    #[test]
+    fn fixed_should_give_value_outside() {
+        assert_eq!(Boundary::Fixed(2).resolve(-1, 5), Position::Outside(2));
+        assert_eq!(Boundary::ZeroPadded.resolve(5, 5), Position::Outside(0));
+        assert_eq!(Boundary::Fixed(2).resolve(4, 5), Position::Inside(4));
+    }
+
+    #[test]
+    fn reflecting_should_mirror_edge() {
+        assert_eq!(Boundary::Reflecting.resolve(-1, 5), Position::Inside(0));
+        assert_eq!(Boundary::Reflecting.resolve(-2, 5), Position::Inside(1));
+        assert_eq!(Boundary::Reflecting.resolve(5, 5), Position::Inside(4));
+        assert_eq!(Boundary::Reflecting.resolve(6, 5), Position::Inside(3));
+        assert_eq!(Boundary::Reflecting.resolve(11, 5), Position::Inside(1));
+    }
+
+    #[test]
+    fn zero_padded_edges_should_not_wrap() {
+        // rule 90 from the right edge: with wrapping the left edge would be set
+        let mut automaton = AutomatonBuilder::new(vec![6])
+            .set_point(&[5])
+            .unwrap()
+            .set_boundaries(vec![Boundary::ZeroPadded])
+            .unwrap()
+            .set_elementary_rule(90)
+            .build()
+            .unwrap();
+        automaton.advance().unwrap();
+        assert_eq!(automaton.grid().values(), vec![0, 0, 0, 0, 1, 0]);
+    }
+}
@@ -0,0 +1,150 @@
+use crate::automaton::neighborhood::Neighborhood;
+use crate::automaton::rules::Rules;
+
+/// largest lookup table compiled
+pub const MAX_TABLE_SIZE: usize = 1 << 18;
+
+/// entry of a neighborhood no rule matches, when that is an error
+const NO_RULE: u32 = u32::MAX;
+
+/// rules compiled into a lookup table, one entry per neighborhood
+#[derive(Clone)]
+pub struct CompiledRules {
+    rules: Rules,
+    table: Option<Vec<u32>>,
+    neighbors: usize,
+}
+
+impl CompiledRules {
+    /// compile rules, if the table is small enough
+    pub fn new(rules: Rules, neighbors: usize) -> Self {
+        let size = table_size(rules.states(), neighbors).filter(|_| rules.is_deterministic());
+        let table = size.map(|size| {
+            let states = rules.states();
+            (0..size)
+                .map(|index| {
+                    rules
+                        .next_state(&Neighborhood::from_index(index, states, neighbors))
+                        .unwrap_or(NO_RULE)
+                })
+                .collect()
+        });
+        Self {
+            rules,
+            table,
+            neighbors,
+        }
+    }
+
+    /// whether neighborhoods are looked up in a table
+    pub fn is_compiled(&self) -> bool {
+        self.table.is_some()
+    }
+
+    pub fn rules(&self) -> &Rules {
+        &self.rules
+    }
+
+    /// next value of a cell
+    pub fn next_state(&self, neighborhood: &Neighborhood, random: u64) -> Option<u32> {
+        if let Some(table) = &self.table {
+            if let Some(index) = self.encode(neighborhood) {
+                return match table[index] {
+                    NO_RULE => None,
+                    next => Some(next),
+                };
+            }
+        }
+        self.rules.next_state_random(neighborhood, random)
+    }
+
+    /// index of a neighborhood in the table
+    fn encode(&self, neighborhood: &Neighborhood) -> Option<usize> {
+        let states = self.rules.states();
+        if neighborhood.neighbors().len() != self.neighbors {
+            return None;
+        }
+        let mut index = 0;
+        for value in std::iter::once(&neighborhood.cell()).chain(neighborhood.neighbors()) {
+            if *value >= states {
+                return None;
+            }
+            index = index * states as usize + *value as usize;
+        }
+        Some(index)
+    }
+}
+
+/// number of entries in the table, if it is small enough to build
+fn table_size(states: u32, neighbors: usize) -> Option<usize> {
+    let mut size: usize = 1;
+    for _ in 0..=neighbors {
+        size = size.checked_mul(states as usize)?;
+        if size > MAX_TABLE_SIZE {
+            return None;
+        }
+    }
+    Some(size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::automaton::test_configs::config_rules;
+
+    #[test]
+    fn compiled_should_agree_with_rules() {
+        for path in &[
+            "game_of_life/rules_explicit.json",
+            "game_of_life/rules_sum.json",
+            "brians_brain/rules.json",
+        ] {
+            let rules = config_rules(path);
+            let compiled = CompiledRules::new(rules.clone(), 8);
+            assert!(compiled.is_compiled());
+            let states = rules.states();
+            for index in 0..states.pow(9) as usize {
+                let neighborhood = Neighborhood::from_index(index, states, 8);
+                assert_eq!(
+                    compiled.next_state(&neighborhood, 0),
+                    rules.next_state(&neighborhood)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn encode_should_invert_from_index() {
+        let compiled = CompiledRules::new(config_rules("brians_brain/rules.json"), 8);
+        for index in &[0, 1, 2, 3, 100, 3usize.pow(9) - 1] {
+            assert_eq!(
+                compiled.encode(&Neighborhood::from_index(*index, 3, 8)),
+                Some(*index)
+            );
+        }
+    }
+
+    #[test]
+    fn large_tables_should_fall_back_to_rules() {
+        let rules = Rules::from_rulestring("B3/S23").unwrap();
+        // 2^25 entries for a radius 2 Moore neighborhood in 2 dimensions
+        let compiled = CompiledRules::new(rules.clone(), 24);
+        assert!(!compiled.is_compiled());
+        let neighborhood = Neighborhood::new(vec![1; 24], 1);
+        assert_eq!(
+            compiled.next_state(&neighborhood, 0),
+            rules.next_state(&neighborhood)
+        );
+    }
+
+    #[test]
+    fn values_outside_states_should_fall_back_to_rules() {
+        let rules = config_rules("game_of_life/rules_sum.json");
+        let compiled = CompiledRules::new(rules.clone(), 8);
+        let neighborhood = Neighborhood::new(vec![3, 0, 0, 0, 0, 0, 0, 0], 0);
+        assert_eq!(
+            compiled.next_state(&neighborhood, 0),
+            rules.next_state(&neighborhood)
+        );
+    }
+}
@@ -1,19 +1,97 @@
-use crate::automaton::neighborhood::Neighborhood;
-use exitfailure::ExitFailure;
-pub use ndarray::{iter::IndexedIter as Iterator, ArrayD, Dim, IxDyn};
+use crate::automaton::bitgrid::BitGrid;
+use crate::automaton::boundary::{Boundary, Position};
+use crate::automaton::neighborhood::{Neighborhood, NeighborhoodShape};
+use crate::automaton::palette::Palette;
+use crate::error::Error;
+use crate::utils::{coordinate_of, index_of};
+pub use ndarray::{ArrayD, ArrayViewD, Axis, Dim, IxDyn};
 use std::fmt;
 
+/// cell values addressed by linear index in row-major order
+pub trait CellStorage {
+    fn len(&self) -> usize;
+    fn get(&self, index: usize) -> u32;
+    fn set(&mut self, index: usize, value: u32);
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl CellStorage for [u32] {
+    fn len(&self) -> usize {
+        <[u32]>::len(self)
+    }
+
+    fn get(&self, index: usize) -> u32 {
+        self[index]
+    }
+
+    fn set(&mut self, index: usize, value: u32) {
+        self[index] = value;
+    }
+}
+
+/// cells of a grid, one u32 each or packed 64 to a word
+#[derive(Debug, Clone)]
+pub enum Storage {
+    Dense(ArrayD<u32>),
+    Packed(BitGrid),
+}
+
+impl CellStorage for Storage {
+    fn len(&self) -> usize {
+        match self {
+            Storage::Dense(cells) => cells.len(),
+            Storage::Packed(cells) => cells.len(),
+        }
+    }
+
+    fn get(&self, index: usize) -> u32 {
+        match self {
+            Storage::Dense(cells) => dense_values(cells)[index],
+            Storage::Packed(cells) => cells.get(index),
+        }
+    }
+
+    fn set(&mut self, index: usize, value: u32) {
+        match self {
+            Storage::Dense(cells) => dense_values_mut(cells)[index] = value,
+            Storage::Packed(cells) => cells.set(index, value),
+        }
+    }
+}
+
+/// the cells of a dense grid, which is kept in standard layout
+pub fn dense_values(cells: &ArrayD<u32>) -> &[u32] {
+    cells.as_slice().expect("grid is kept in standard layout")
+}
+
+pub fn dense_values_mut(cells: &mut ArrayD<u32>) -> &mut [u32] {
+    cells
+        .as_slice_mut()
+        .expect("grid is kept in standard layout")
+}
+
 #[derive(Debug, Clone)]
 pub struct Grid {
     dims: Vec<usize>,
-    grid: ArrayD<u32>,
+    cells: Storage,
+    boundaries: Vec<Boundary>,
 }
 
 impl Grid {
     pub fn new(_dims: Vec<usize>, _grid: ArrayD<u32>) -> Self {
+        // keep cells in row-major order so they can be read by linear index
+        let _grid = if _grid.is_standard_layout() {
+            _grid
+        } else {
+            _grid.as_standard_layout().into_owned()
+        };
         Self {
+            boundaries: vec![Boundary::default(); _dims.len()],
             dims: _dims,
-            grid: _grid,
+            cells: Storage::Dense(_grid),
         }
     }
 
@@ -21,78 +99,195 @@ impl Grid {
         &self.dims[..]
     }
 
+    pub fn boundaries(&self) -> &[Boundary] {
+        &self.boundaries[..]
+    }
+
+    /// set the boundary of every axis
+    pub fn set_boundaries(&mut self, boundaries: Vec<Boundary>) -> Result<(), Error> {
+        self.boundaries = match boundaries.len() {
+            1 => vec![boundaries[0]; self.dims.len()],
+            len if len == self.dims.len() => boundaries,
+            _ => {
+                let cause = format!(
+                    "expected 1 or {} boundaries, got {}",
+                    self.dims.len(),
+                    boundaries.len()
+                );
+                return Err(Error::Grid(cause));
+            }
+        };
+        Ok(())
+    }
+
+    pub fn storage(&self) -> &Storage {
+        &self.cells
+    }
+
+    pub fn storage_mut(&mut self) -> &mut Storage {
+        &mut self.cells
+    }
+
+    pub fn is_packed(&self) -> bool {
+        matches!(self.cells, Storage::Packed(_))
+    }
+
+    /// pack the cells 64 to a word
+    pub fn pack(&mut self) -> Result<(), Error> {
+        if let Storage::Dense(cells) = &self.cells {
+            if let Some(index) = dense_values(cells).iter().position(|value| *value > 1) {
+                let cause = format!(
+                    "cannot pack point {:?} with value {}, only 0 and 1 can be packed",
+                    coordinate_of(index, &self.dims),
+                    dense_values(cells)[index]
+                );
+                return Err(Error::Grid(cause));
+            }
+            let mut packed = BitGrid::new(&self.dims);
+            for (index, value) in dense_values(cells).iter().enumerate() {
+                packed.set(index, *value);
+            }
+            self.cells = Storage::Packed(packed);
+        }
+        Ok(())
+    }
+
+    /// keep one `u32` per cell
+    pub fn unpack(&mut self) {
+        if self.is_packed() {
+            self.cells = Storage::Dense(self.grid());
+        }
+    }
+
+    /// value at an offset from a point, following the boundaries
+    pub fn get_offset_value(&self, point: &[usize], offset: &[i32]) -> Result<u32, Error> {
+        let mut resolved = Vec::with_capacity(point.len());
+        for (axis, (coordinate, delta)) in point.iter().zip(offset).enumerate() {
+            let target = *coordinate as i64 + i64::from(*delta);
+            match self.boundaries[axis].resolve(target, self.dims[axis]) {
+                Position::Inside(index) => resolved.push(index),
+                Position::Outside(value) => return Ok(value),
+            }
+        }
+        self.get_point_value(&resolved[..])
+    }
+
     pub fn grid(&self) -> ArrayD<u32> {
-        self.grid.clone()
+        match &self.cells {
+            Storage::Dense(cells) => cells.clone(),
+            Storage::Packed(_) => ArrayD::from_shape_vec(IxDyn(&self.dims[..]), self.values())
+                .expect("a packed grid holds a value for every cell"),
+        }
+    }
+
+    /// every cell value in row-major order
+    pub fn values(&self) -> Vec<u32> {
+        match &self.cells {
+            Storage::Dense(cells) => dense_values(cells).to_vec(),
+            Storage::Packed(cells) => (0..cells.len()).map(|index| cells.get(index)).collect(),
+        }
     }
 
-    pub fn get_point_value(&self, point: &[usize]) -> Result<u32, ExitFailure> {
+    pub fn get_point_value(&self, point: &[usize]) -> Result<u32, Error> {
         if self.dims.len() != point.len() {
-            return Err(ExitFailure::from(GridError::new(
-                "point is of wrong dimensions!",
-            )));
+            return Err(Error::DimensionMismatch {
+                expected: self.dims.len(),
+                found: point.len(),
+            });
         }
-        match self.grid.get(IxDyn(point)) {
-            Some(val) => return Ok(*val),
-            None => return Err(ExitFailure::from(GridError::new("point does not exist"))),
+        match index_of(point, &self.dims) {
+            Some(index) => Ok(self.cells.get(index)),
+            None => Err(Error::OutOfBounds {
+                point: point.to_vec(),
+                dims: self.dims.clone(),
+            }),
         }
     }
 
-    pub fn neighborhood(&self, point: Vec<usize>) -> Result<Neighborhood, ExitFailure> {
+    pub fn neighborhood(
+        &self,
+        point: Vec<usize>,
+        shape: &NeighborhoodShape,
+    ) -> Result<Neighborhood, Error> {
         if self.dims.len() != point.len() {
-            return Err(ExitFailure::from(GridError::new(
-                "neighborhood: base point is of wrong dimensions!",
-            )));
+            return Err(Error::DimensionMismatch {
+                expected: self.dims.len(),
+                found: point.len(),
+            });
         }
-        Ok(Neighborhood::derive(&self, &point)?)
+        Neighborhood::derive(self, &point, shape)
     }
 
-    pub fn set_point(&mut self, point: &[usize], value: u32) -> Result<(), ExitFailure> {
+    pub fn set_point(&mut self, point: &[usize], value: u32) -> Result<(), Error> {
         if self.dims.len() != point.len() {
+            return Err(Error::DimensionMismatch {
+                expected: self.dims.len(),
+                found: point.len(),
+            });
+        }
+        let index = match index_of(point, &self.dims) {
+            Some(index) => index,
+            None => {
+                return Err(Error::OutOfBounds {
+                    point: point.to_vec(),
+                    dims: self.dims.clone(),
+                });
+            }
+        };
+        if value > 1 && self.is_packed() {
             let cause = format!(
-                "added point is of wrong dimensions! expected {:?}, got {:?}",
-                self.dims, point
+                "cannot set point {:?} to {}, a packed grid only holds 0 and 1",
+                point, value
             );
-
-            return Err(ExitFailure::from(GridError::new(&cause[..])));
+            return Err(Error::Grid(cause));
         }
-        self.grid[IxDyn(point)] = value;
+        self.cells.set(index, value);
         Ok(())
     }
 
-    pub fn iter(&self) -> Iterator<u32, IxDyn> {
-        self.grid.indexed_iter()
+    /// every point of the grid with its value, in row-major order
+    pub fn iter(&self) -> impl Iterator<Item = (Vec<usize>, u32)> + '_ {
+        (0..self.cells.len())
+            .map(move |index| (coordinate_of(index, &self.dims), self.cells.get(index)))
     }
-}
 
-impl fmt::Display for Grid {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let mut fmt = format!("\n{}", self.grid);
-        fmt = fmt.replace("[", "");
-        fmt = fmt.replace("[", "");
-        fmt = fmt.replace("]", "");
-        fmt = fmt.replace("1", "\x1b[32m▊\x1b");
-        fmt = fmt.replace("0", "\x1b[90m▊\x1b");
-        fmt = fmt.replace(",", "");
-        write!(f, "{}{}", fmt, "[0m")
+    /// draw the grid with a colored block per cell
+    pub fn render(&self, palette: &Palette) -> String {
+        self.render_with(&|state| palette.paint(state))
+    }
+
+    /// write the state of every cell, laid out as by render
+    pub fn render_plain(&self) -> String {
+        self.render_with(&|state| state.to_string())
+    }
+
+    fn render_with(&self, paint: &dyn Fn(u32) -> String) -> String {
+        let mut out = String::new();
+        match &self.cells {
+            Storage::Dense(cells) => render_view(cells.view(), paint, &mut out),
+            Storage::Packed(_) => render_view(self.grid().view(), paint, &mut out),
+        }
+        out
     }
-}
-#[derive(Debug)]
-struct GridError {
-    cause: String,
 }
 
-impl GridError {
-    pub fn new(cause: &str) -> Self {
-        Self {
-            cause: cause.to_string(),
+fn render_view(view: ArrayViewD<u32>, paint: &dyn Fn(u32) -> String, out: &mut String) {
+    if view.ndim() <= 1 {
+        let row: Vec<String> = view.iter().map(|state| paint(*state)).collect();
+        out.push_str(&row.join(" "));
+        out.push('\n');
+        return;
+    }
+    for (i, sub) in view.axis_iter(Axis(0)).enumerate() {
+        if i > 0 && view.ndim() > 2 {
+            out.push('\n');
         }
+        render_view(sub, paint, out);
     }
 }
 
-impl fmt::Display for GridError {
+impl fmt::Display for Grid {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "grid error! {}", self.cause)
+        write!(f, "\n{}", self.render(&Palette::default()))
     }
 }
-
-impl std::error::Error for GridError {}
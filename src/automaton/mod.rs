@@ -1,62 +1,1632 @@
 pub mod automaton_builder;
+pub mod bitgrid;
+pub mod block_rule;
+pub mod boundary;
+pub mod compiled_rules;
 pub mod grid;
 pub mod neighborhood;
+pub mod palette;
 pub mod parsers;
+pub mod rule_map;
 pub mod rules;
+pub mod schedule;
+#[cfg(test)]
+pub mod test_configs;
 
+use std::convert::TryFrom;
 use std::fmt;
+use std::fs;
+use std::path::PathBuf;
 
-use crate::automaton::grid::Grid;
+use crate::automaton::bitgrid::{
+    step_elementary, step_hybrid_elementary, step_life_like, BitGrid, BitRow,
+};
+use crate::automaton::block_rule::BlockRule;
+use crate::automaton::boundary::Boundary;
+use crate::automaton::compiled_rules::CompiledRules;
+use crate::automaton::grid::{dense_values, dense_values_mut, CellStorage, Grid, Storage};
+use crate::automaton::neighborhood::{Neighborhood, NeighborhoodTable};
+use crate::automaton::palette::Palette;
+use crate::automaton::parsers::parse_file_to_schema;
+use crate::automaton::parsers::schemas::{AutomatonSchema, BoundarySchema, DimensionsSchema};
+use crate::automaton::rule_map::RuleMap;
 use crate::automaton::rules::Rules;
-use crate::utils::coordinates_iterator::CoordinatesIterator;
-use exitfailure::ExitFailure;
+use crate::automaton::schedule::Schedule;
+use crate::error::Error;
+#[cfg(feature = "parallel")]
+use crate::utils::coordinate_of;
+use crate::utils::split_mix::{derive_seed, SplitMix64};
+use crate::utils::{next_coordinate, set_coordinate_of};
+use ndarray::{ArrayD, IxDyn};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+/// version of the format `Automaton::save` writes
+pub const SAVE_VERSION: u32 = 1;
 
 pub struct Automaton {
     grid: Grid,
+    /// back buffer the next generation is written into before the swap
+    next: Grid,
+    /// previous generation, for second-order automata
+    previous: Option<Grid>,
+    /// generation the automaton became second-order at
+    second_order_since: u32,
+    /// rules of every cell, or the first rule set of a rule map
     rules: Rules,
+    /// rules of each cell of a hybrid automaton
+    rule_map: Option<RuleMap>,
+    /// rule every block of cells follows, in place of `rules`
+    block_rule: Option<BlockRule>,
     gen: u32,
+    /// seed every random value drawn by stochastic rules derives from
+    seed: u64,
+    /// order in which the cells of a generation are updated
+    schedule: Schedule,
+    palette: Palette,
+    /// how generations are computed, chosen on first use
+    stepper: Option<Stepper>,
+    /// scratch space reused for every cell by the serial path
+    #[cfg_attr(feature = "parallel", allow(dead_code))]
+    neighborhood: Neighborhood,
+    #[cfg_attr(feature = "parallel", allow(dead_code))]
+    coordinate: Vec<usize>,
+    /// new values of a block of cells
+    values: Vec<u32>,
 }
 
 impl Automaton {
     pub fn new(grid: Grid, rules: Rules) -> Self {
         Self {
+            next: grid.clone(),
+            previous: None,
+            second_order_since: 0,
+            coordinate: vec![0; grid.dims().len()],
             grid,
             rules,
+            rule_map: None,
+            block_rule: None,
             gen: 0,
+            seed: 0,
+            schedule: Schedule::default(),
+            palette: Palette::default(),
+            stepper: None,
+            neighborhood: Neighborhood::new(Vec::new(), 0),
+            values: Vec::new(),
         }
     }
 
-    pub fn advance(&mut self) -> Result<(), ExitFailure> {
-        let dims = self.grid.dims();
-        let ci = CoordinatesIterator::new(&Vec::from(dims));
-        let mut new_grid = Grid::new(Vec::from(dims), self.grid.grid());
-        for coordinate in ci {
-            let neighborhood = self.grid.neighborhood(coordinate.clone())?;
-            match self.rules.apply(&neighborhood) {
-                Some(val) => new_grid.set_point(&coordinate[..], val)?,
-                _ => continue,
+    pub fn advance(&mut self) -> Result<(), Error> {
+        if self.block_rule.is_some() && self.schedule != Schedule::Synchronous {
+            return Err(Error::Automaton(
+                "a block automaton can only update synchronously".to_string(),
+            ));
+        }
+        self.select_stepper()?;
+        let stepper = self.stepper.as_ref().unwrap();
+        let seed = derive_seed(self.seed, u64::from(self.gen));
+        // the schedule draws from a stream no cell uses
+        let schedule_seed = derive_seed(seed, u64::MAX);
+        if let Schedule::BlockSequential(blocks) = &self.schedule {
+            for block in blocks {
+                stepper.update(
+                    &mut self.grid,
+                    block,
+                    seed,
+                    &mut self.neighborhood,
+                    &mut self.coordinate,
+                    &mut self.values,
+                )?;
+            }
+            self.gen += 1;
+            return Ok(());
+        }
+        if self.schedule.is_sequential() {
+            let cells = self.grid.storage().len();
+            stepper.update_each(
+                &mut self.grid,
+                &self.schedule.sequence(cells, schedule_seed),
+                seed,
+                &mut self.neighborhood,
+                &mut self.coordinate,
+            )?;
+            self.gen += 1;
+            return Ok(());
+        }
+        stepper.step(
+            &self.grid,
+            &mut self.next,
+            self.gen,
+            seed,
+            &mut self.neighborhood,
+            &mut self.coordinate,
+        )?;
+        if let Schedule::RandomIndependent(probability) = self.schedule {
+            // cells that are not updated keep their value
+            let current = self.grid.storage();
+            let next = self.next.storage_mut();
+            for cell in 0..current.len() {
+                if SplitMix64::new(derive_seed(schedule_seed, cell as u64)).next_f64()
+                    >= probability
+                {
+                    next.set(cell, current.get(cell));
+                }
             }
         }
-        self.grid = new_grid.clone();
+        std::mem::swap(&mut self.grid, &mut self.next);
+        if let Some(previous) = &mut self.previous {
+            subtract_cells(
+                self.grid.storage_mut(),
+                previous.storage(),
+                self.rules.states(),
+            );
+            // the spare buffer now holds the generation before the previous one
+            std::mem::swap(previous, &mut self.next);
+        }
         self.gen += 1;
         Ok(())
     }
 
-    pub fn advance_multi(&mut self, gens: u32) -> Result<(), ExitFailure> {
+    /// step the automaton back one generation
+    pub fn retreat(&mut self) -> Result<(), Error> {
+        let inverse = match (&self.previous, &self.block_rule) {
+            (None, Some(rule)) => Some(rule.inverse().ok_or_else(|| {
+                Error::Automaton(
+                    "a block rule that maps two blocks to the same block cannot retreat"
+                        .to_string(),
+                )
+            })?),
+            (None, None) => {
+                return Err(Error::Automaton(
+                    "only a second-order or block automaton can retreat".to_string(),
+                ));
+            }
+            _ => None,
+        };
+        if self.gen == 0 {
+            return Err(Error::Automaton(
+                "cannot retreat past generation 0".to_string(),
+            ));
+        }
+        if inverse.is_none() && self.gen <= self.second_order_since {
+            return Err(Error::Automaton(format!(
+                "cannot retreat past generation {}, where the automaton became second-order",
+                self.second_order_since
+            )));
+        }
+        if let Some(inverse) = inverse {
+            Stepper::Block(inverse).step(
+                &self.grid,
+                &mut self.next,
+                self.gen - 1,
+                0,
+                &mut self.neighborhood,
+                &mut self.coordinate,
+            )?;
+            std::mem::swap(&mut self.grid, &mut self.next);
+            self.gen -= 1;
+            return Ok(());
+        }
+        self.select_stepper()?;
+        let previous = self.previous.as_mut().unwrap();
+        self.stepper.as_ref().unwrap().step(
+            previous,
+            &mut self.next,
+            self.gen - 1,
+            derive_seed(self.seed, u64::from(self.gen - 1)),
+            &mut self.neighborhood,
+            &mut self.coordinate,
+        )?;
+        subtract_cells(
+            self.next.storage_mut(),
+            self.grid.storage(),
+            self.rules.states(),
+        );
+        std::mem::swap(&mut self.grid, previous);
+        std::mem::swap(previous, &mut self.next);
+        self.gen -= 1;
+        Ok(())
+    }
+
+    /// choose how generations are computed
+    fn select_stepper(&mut self) -> Result<(), Error> {
+        if self.stepper.is_none() {
+            let map = self.rule_map.as_ref();
+            self.stepper = Some(if let Some(rule) = &self.block_rule {
+                Stepper::Block(rule.clone())
+            } else if self.schedule.is_sequential() {
+                Stepper::cells(&self.grid, &self.rules, map)?
+            } else {
+                Stepper::select(&self.grid, &self.rules, map)?
+            });
+        }
+        Ok(())
+    }
+
+    /// run as a second-order automaton, which can retreat
+    pub fn set_second_order(&mut self, second_order: bool) -> Result<(), Error> {
+        if second_order && self.schedule != Schedule::Synchronous {
+            return Err(Error::SecondOrderSchedule);
+        }
+        if !second_order {
+            self.previous = None;
+        } else if self.previous.is_none() {
+            self.second_order_since = self.gen;
+            let mut previous = self.grid.clone();
+            let cells = previous.storage_mut();
+            for index in 0..cells.len() {
+                cells.set(index, 0);
+            }
+            self.previous = Some(previous);
+        }
+        Ok(())
+    }
+
+    pub fn is_second_order(&self) -> bool {
+        self.previous.is_some()
+    }
+
+    /// previous generation of a second-order automaton
+    pub fn previous(&self) -> Option<&Grid> {
+        self.previous.as_ref()
+    }
+
+    /// give each cell its own rules
+    pub fn set_rule_map(&mut self, map: RuleMap) -> Result<(), Error> {
+        if map.dims() != self.grid.dims() {
+            let cause = format!(
+                "rule map of dimensions {:?} does not fit grid of dimensions {:?}",
+                map.dims(),
+                self.grid.dims()
+            );
+            return Err(Error::Automaton(cause));
+        }
+        self.rules = map.rules()[0].clone();
+        self.rule_map = Some(map);
+        self.stepper = None;
+        Ok(())
+    }
+
+    pub fn rule_map(&self) -> Option<&RuleMap> {
+        self.rule_map.as_ref()
+    }
+
+    /// update the grid in blocks
+    pub fn set_block_rule(&mut self, rule: BlockRule) -> Result<(), Error> {
+        rule.check(self.grid.dims(), self.grid.boundaries())?;
+        let mut rules = Rules::new(Vec::new());
+        rules.set_states(rule.states());
+        self.rules = rules;
+        self.rule_map = None;
+        self.block_rule = Some(rule);
+        self.stepper = None;
+        Ok(())
+    }
+
+    pub fn block_rule(&self) -> Option<&BlockRule> {
+        self.block_rule.as_ref()
+    }
+
+    /// set the seed of stochastic rules
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// set the order cells are updated in
+    pub fn set_schedule(&mut self, schedule: Schedule) -> Result<(), Error> {
+        schedule.check(self.grid.storage().len())?;
+        if self.previous.is_some() && schedule != Schedule::Synchronous {
+            return Err(Error::SecondOrderSchedule);
+        }
+        self.schedule = schedule;
+        self.stepper = None;
+        Ok(())
+    }
+
+    pub fn schedule(&self) -> &Schedule {
+        &self.schedule
+    }
+
+    pub fn advance_multi(&mut self, gens: u32) -> Result<(), Error> {
         for _ in 0..gens {
             self.advance()?;
         }
         Ok(())
     }
 
-    pub fn set_point(&mut self, point: &[usize]) -> Result<(), ExitFailure> {
+    pub fn set_point(&mut self, point: &[usize]) -> Result<(), Error> {
         self.grid.set_point(point, 1)?;
         Ok(())
     }
+
+    pub fn set_point_value(&mut self, point: &[usize], value: u32) -> Result<(), Error> {
+        self.grid.set_point(point, value)?;
+        Ok(())
+    }
+
+    pub fn grid(&self) -> &Grid {
+        &self.grid
+    }
+
+    pub fn generation(&self) -> u32 {
+        self.gen
+    }
+
+    pub fn set_palette(&mut self, palette: Palette) {
+        self.palette = palette;
+    }
+
+    /// full state of the automaton
+    pub fn to_schema(&self) -> Result<AutomatonSchema, Error> {
+        let dims = self.grid.dims();
+        let mut rules = self.rules.to_schema()?;
+        rules.palette = Some(self.palette.colors().to_vec());
+        rules.schedule = Some(self.schedule.to_schema(dims));
+        rules.block_rule = self.block_rule.as_ref().map(BlockRule::to_schema);
+        Ok(AutomatonSchema {
+            version: SAVE_VERSION,
+            generation: self.gen,
+            seed: self.seed,
+            dimensions: DimensionsSchema {
+                dimensions: dims.to_vec(),
+                boundary: self
+                    .grid
+                    .boundaries()
+                    .iter()
+                    .map(BoundarySchema::from)
+                    .collect(),
+            },
+            cells: self.grid.values(),
+            previous: self.previous.as_ref().map(Grid::values),
+            second_order_since: self.second_order_since,
+            rules,
+            rule_map: match &self.rule_map {
+                Some(map) => Some(map.to_schema()?),
+                None => None,
+            },
+        })
+    }
+
+    /// restore an automaton from its full state
+    pub fn from_schema(schema: &AutomatonSchema) -> Result<Self, Error> {
+        if schema.version != SAVE_VERSION {
+            let cause = format!(
+                "cannot restore state of version {}, expected {}",
+                schema.version, SAVE_VERSION
+            );
+            return Err(Error::Automaton(cause));
+        }
+        let dims = &schema.dimensions.dimensions;
+        let boundaries: Vec<Boundary> = schema
+            .dimensions
+            .boundary
+            .iter()
+            .map(Boundary::from)
+            .collect();
+        let rules = Rules::try_from(&schema.rules)?;
+        let states = rules.states();
+        let block_rule = match &schema.rules.block_rule {
+            Some(block_rule) => Some(BlockRule::from_schema(block_rule, states, dims.len())?),
+            None => None,
+        };
+        let rule_map = match &schema.rule_map {
+            Some(map) => Some(RuleMap::from_schema(map, dims)?),
+            None => None,
+        };
+        if block_rule.is_none() {
+            let all_rules = match &rule_map {
+                Some(map) => map.rules().iter().collect(),
+                None => vec![&rules],
+            };
+            for rules in all_rules {
+                if rules.is_empty() {
+                    return Err(Error::EmptyRules);
+                }
+                rules.check_shape(dims.len())?;
+            }
+        }
+        let grid = saved_grid(&schema.cells, dims, &boundaries, states)?;
+        let mut automaton = Automaton::new(grid, rules);
+        if let Some(previous) = &schema.previous {
+            automaton.previous = Some(saved_grid(previous, dims, &boundaries, states)?);
+        }
+        if let Some(map) = rule_map {
+            automaton.set_rule_map(map)?;
+        }
+        if let Some(rule) = block_rule {
+            automaton.set_block_rule(rule)?;
+        }
+        if let Some(colors) = &schema.rules.palette {
+            automaton.set_palette(Palette::new(colors.clone()));
+        }
+        if let Some(schedule) = &schema.rules.schedule {
+            automaton.set_schedule(Schedule::from_schema(schedule, dims)?)?;
+        }
+        automaton.set_seed(schema.seed);
+        automaton.gen = schema.generation;
+        automaton.second_order_since = schema.second_order_since;
+        Ok(automaton)
+    }
+
+    /// write the full state of the automaton to a file
+    pub fn save(&self, path: &PathBuf) -> Result<(), Error> {
+        let data = serde_json::to_string_pretty(&self.to_schema()?)
+            .map_err(|e| Error::Automaton(e.to_string()))?;
+        fs::write(path, data).map_err(|source| Error::Io {
+            path: path.clone(),
+            source,
+        })
+    }
+
+    /// restore an automaton from a file written by `save`
+    pub fn load(path: &PathBuf) -> Result<Self, Error> {
+        Automaton::from_schema(&parse_file_to_schema(path)?)
+    }
+}
+
+/// a grid holding saved cell values, packed if the cells are binary
+fn saved_grid(
+    cells: &[u32],
+    dims: &[usize],
+    boundaries: &[Boundary],
+    states: u32,
+) -> Result<Grid, Error> {
+    if let Some(value) = cells.iter().find(|value| **value >= states) {
+        let cause = format!(
+            "saved cell has value {} but only {} states are declared",
+            value, states
+        );
+        return Err(Error::Automaton(cause));
+    }
+    let cells = ArrayD::from_shape_vec(IxDyn(dims), cells.to_vec()).map_err(|_| {
+        let cause = format!(
+            "expected {} saved cells, got {}",
+            dims.iter().product::<usize>(),
+            cells.len()
+        );
+        Error::Automaton(cause)
+    })?;
+    let mut grid = Grid::new(dims.to_vec(), cells);
+    if !boundaries.is_empty() {
+        grid.set_boundaries(boundaries.to_vec())?;
+    }
+    if states == 2 {
+        grid.pack()?;
+    }
+    Ok(grid)
+}
+
+/// how a generation is computed
+#[allow(clippy::large_enum_variant)]
+enum Stepper {
+    /// a word of a packed one-dimensional grid at a time
+    Elementary(u8),
+    /// a word of a packed grid at a time, with a rule per cell
+    HybridElementary(Vec<(u8, BitGrid)>),
+    /// a word of a packed two-dimensional grid at a time
+    LifeLike { birth: u16, survival: u16 },
+    /// cell by cell, reading neighborhoods through the table
+    Cells {
+        table: NeighborhoodTable,
+        rules: CellRules,
+    },
+    /// a block of cells at a time, with blocks shifted on odd generations
+    Block(BlockRule),
+}
+
+impl Stepper {
+    /// the fastest way to step a grid by the given rules
+    fn select(grid: &Grid, rules: &Rules, map: Option<&RuleMap>) -> Result<Self, Error> {
+        // a fixed edge of a value other than 0 or 1 is not binary
+        let binary_edges = grid.boundaries().iter().all(|boundary| match boundary {
+            Boundary::Fixed(value) => *value <= 1,
+            _ => true,
+        });
+        let words = match (grid.dims().len(), map) {
+            _ if !grid.is_packed() || !binary_edges => None,
+            (1, None) => rules.as_elementary().map(Stepper::Elementary),
+            (1, Some(map)) => map.as_elementary().map(Stepper::HybridElementary),
+            (2, None) => rules
+                .as_life_like()
+                .map(|(birth, survival)| Stepper::LifeLike { birth, survival }),
+            _ => None,
+        };
+        if let Some(stepper) = words {
+            return Ok(stepper);
+        }
+        Stepper::cells(grid, rules, map)
+    }
+
+    /// step a grid cell by cell, reading neighborhoods through a table
+    fn cells(grid: &Grid, rules: &Rules, map: Option<&RuleMap>) -> Result<Self, Error> {
+        let table = NeighborhoodTable::new(grid, rules.shape())?;
+        let rules = match map {
+            None => CellRules {
+                rules: vec![CompiledRules::new(rules.clone(), table.len())],
+                cells: None,
+                deterministic: rules.is_deterministic(),
+            },
+            Some(map) => CellRules {
+                rules: map
+                    .rules()
+                    .iter()
+                    .map(|rules| CompiledRules::new(rules.clone(), table.len()))
+                    .collect(),
+                cells: Some(map.cells().to_vec()),
+                deterministic: map.rules().iter().all(Rules::is_deterministic),
+            },
+        };
+        Ok(Stepper::Cells { table, rules })
+    }
+
+    /// compute the next generation of source into out
+    fn step(
+        &self,
+        source: &Grid,
+        out: &mut Grid,
+        generation: u32,
+        seed: u64,
+        neighborhood: &mut Neighborhood,
+        coordinate: &mut [usize],
+    ) -> Result<(), Error> {
+        let dims = source.dims();
+        let boundaries = source.boundaries();
+        match (self, source.storage(), out.storage_mut()) {
+            (Stepper::Block(rule), cells, out) => {
+                rule.step(cells, out, dims, boundaries, generation % 2 == 1);
+                Ok(())
+            }
+            (Stepper::Elementary(rule), Storage::Packed(cells), Storage::Packed(out)) => {
+                step_elementary(cells, out, *rule, boundaries[0]);
+                Ok(())
+            }
+            (Stepper::HybridElementary(rules), Storage::Packed(cells), Storage::Packed(out)) => {
+                step_hybrid_elementary(cells, out, rules, boundaries[0]);
+                Ok(())
+            }
+            (
+                Stepper::LifeLike { birth, survival },
+                Storage::Packed(cells),
+                Storage::Packed(out),
+            ) => {
+                step_life_like(cells, out, *birth, *survival, boundaries);
+                Ok(())
+            }
+            (Stepper::Cells { table, rules }, Storage::Packed(cells), Storage::Packed(out)) => {
+                step_packed(
+                    table,
+                    rules,
+                    cells,
+                    dims,
+                    out,
+                    seed,
+                    neighborhood,
+                    coordinate,
+                )
+            }
+            (Stepper::Cells { table, rules }, Storage::Dense(cells), Storage::Dense(out)) => {
+                step_dense(
+                    table,
+                    rules,
+                    dense_values(cells),
+                    dims,
+                    dense_values_mut(out),
+                    seed,
+                    neighborhood,
+                    coordinate,
+                )
+            }
+            _ => unreachable!("every buffer is cloned from the same grid"),
+        }
+    }
+
+    /// update cells in place all at once
+    fn update(
+        &self,
+        grid: &mut Grid,
+        cells: &[usize],
+        seed: u64,
+        neighborhood: &mut Neighborhood,
+        coordinate: &mut [usize],
+        values: &mut Vec<u32>,
+    ) -> Result<(), Error> {
+        let (table, rules) = self.cell_rules();
+        values.clear();
+        let mut last = None;
+        for cell in cells {
+            seek(coordinate, last, *cell, grid.dims());
+            values.push(next_value(
+                table,
+                rules,
+                grid.storage(),
+                *cell,
+                coordinate,
+                seed,
+                neighborhood,
+            )?);
+            last = Some(*cell);
+        }
+        let storage = grid.storage_mut();
+        for (cell, value) in cells.iter().zip(values.iter()) {
+            storage.set(*cell, *value);
+        }
+        Ok(())
+    }
+
+    /// update cells in place one after another
+    fn update_each(
+        &self,
+        grid: &mut Grid,
+        cells: &[usize],
+        seed: u64,
+        neighborhood: &mut Neighborhood,
+        coordinate: &mut [usize],
+    ) -> Result<(), Error> {
+        let (table, rules) = self.cell_rules();
+        let mut last = None;
+        for cell in cells {
+            seek(coordinate, last, *cell, grid.dims());
+            let value = next_value(
+                table,
+                rules,
+                grid.storage(),
+                *cell,
+                coordinate,
+                seed,
+                neighborhood,
+            )?;
+            grid.storage_mut().set(*cell, value);
+            last = Some(*cell);
+        }
+        Ok(())
+    }
+
+    fn cell_rules(&self) -> (&NeighborhoodTable, &CellRules) {
+        match self {
+            Stepper::Cells { table, rules } => (table, rules),
+            _ => unreachable!("cells updated in place are stepped one by one"),
+        }
+    }
+}
+
+/// move a coordinate from one linear index to another
+fn seek(coordinate: &mut [usize], from: Option<usize>, to: usize, dims: &[usize]) {
+    match from {
+        Some(from) if from + 1 == to => next_coordinate(coordinate, dims),
+        _ => set_coordinate_of(coordinate, to, dims),
+    }
+}
+
+/// compiled rules of every cell
+struct CellRules {
+    rules: Vec<CompiledRules>,
+    /// rules index of each cell, for a rule map
+    cells: Option<Vec<usize>>,
+    /// whether no cell needs random values
+    deterministic: bool,
+}
+
+impl CellRules {
+    /// the rules of the cell at a linear index
+    fn of(&self, index: usize) -> &CompiledRules {
+        match &self.cells {
+            Some(cells) => &self.rules[cells[index]],
+            None => &self.rules[0],
+        }
+    }
+}
+
+/// subtract other from cells, modulo states
+fn subtract_cells(cells: &mut Storage, other: &Storage, states: u32) {
+    match (cells, other) {
+        (Storage::Packed(cells), Storage::Packed(other)) => {
+            for (word, other) in cells.words_mut().iter_mut().zip(other.words()) {
+                *word ^= *other;
+            }
+        }
+        (Storage::Dense(cells), Storage::Dense(other)) => {
+            let cells = dense_values_mut(cells);
+            for (value, other) in cells.iter_mut().zip(dense_values(other)) {
+                *value = (*value % states + states - *other % states) % states;
+            }
+        }
+        _ => unreachable!("every buffer is cloned from the same grid"),
+    }
+}
+
+/// number of cells each thread works on at a time
+#[cfg(feature = "parallel")]
+const PARALLEL_CHUNK_SIZE: usize = 4096;
+
+/// step a dense grid cell by cell
+#[cfg_attr(feature = "parallel", allow(unused_variables))]
+#[allow(clippy::too_many_arguments)]
+fn step_dense(
+    table: &NeighborhoodTable,
+    rules: &CellRules,
+    values: &[u32],
+    dims: &[usize],
+    out: &mut [u32],
+    seed: u64,
+    neighborhood: &mut Neighborhood,
+    coordinate: &mut [usize],
+) -> Result<(), Error> {
+    #[cfg(not(feature = "parallel"))]
+    return step_cells(
+        table,
+        rules,
+        values,
+        dims,
+        0,
+        out,
+        seed,
+        neighborhood,
+        coordinate,
+    );
+
+    #[cfg(feature = "parallel")]
+    out.par_chunks_mut(PARALLEL_CHUNK_SIZE)
+        .enumerate()
+        .try_for_each(|(chunk, out)| {
+            let start = chunk * PARALLEL_CHUNK_SIZE;
+            let mut neighborhood = Neighborhood::new(Vec::with_capacity(table.len()), 0);
+            let mut coordinate = coordinate_of(start, dims);
+            step_cells(
+                table,
+                rules,
+                values,
+                dims,
+                start,
+                out,
+                seed,
+                &mut neighborhood,
+                &mut coordinate,
+            )
+        })
+}
+
+/// step a packed grid cell by cell, a row at a time
+#[cfg_attr(feature = "parallel", allow(unused_variables))]
+#[allow(clippy::too_many_arguments)]
+fn step_packed(
+    table: &NeighborhoodTable,
+    rules: &CellRules,
+    cells: &BitGrid,
+    dims: &[usize],
+    out: &mut BitGrid,
+    seed: u64,
+    neighborhood: &mut Neighborhood,
+    coordinate: &mut [usize],
+) -> Result<(), Error> {
+    let row_len = cells.row_len();
+    let words_per_row = cells.words_per_row();
+    if words_per_row == 0 {
+        return Ok(());
+    }
+
+    #[cfg(not(feature = "parallel"))]
+    return out
+        .words_mut()
+        .chunks_mut(words_per_row)
+        .enumerate()
+        .try_for_each(|(row, words)| {
+            step_cells(
+                table,
+                rules,
+                cells,
+                dims,
+                row * row_len,
+                &mut BitRow::new(words, row_len),
+                seed,
+                neighborhood,
+                coordinate,
+            )
+        });
+
+    #[cfg(feature = "parallel")]
+    out.words_mut()
+        .par_chunks_mut(words_per_row)
+        .enumerate()
+        .try_for_each(|(row, words)| {
+            let start = row * row_len;
+            let mut neighborhood = Neighborhood::new(Vec::with_capacity(table.len()), 0);
+            let mut coordinate = coordinate_of(start, dims);
+            step_cells(
+                table,
+                rules,
+                cells,
+                dims,
+                start,
+                &mut BitRow::new(words, row_len),
+                seed,
+                &mut neighborhood,
+                &mut coordinate,
+            )
+        })
+}
+
+/// compute the next values of cells from start on into out
+#[allow(clippy::too_many_arguments)]
+fn step_cells<S, O>(
+    table: &NeighborhoodTable,
+    rules: &CellRules,
+    values: &S,
+    dims: &[usize],
+    start: usize,
+    out: &mut O,
+    seed: u64,
+    neighborhood: &mut Neighborhood,
+    coordinate: &mut [usize],
+) -> Result<(), Error>
+where
+    S: CellStorage + ?Sized,
+    O: CellStorage + ?Sized,
+{
+    for offset in 0..out.len() {
+        let index = start + offset;
+        let next_value = next_value(table, rules, values, index, coordinate, seed, neighborhood)?;
+        out.set(offset, next_value);
+        // wraps back to the origin after the last cell
+        next_coordinate(coordinate, dims);
+    }
+    Ok(())
+}
+
+/// next value of the cell at a linear index
+fn next_value<S: CellStorage + ?Sized>(
+    table: &NeighborhoodTable,
+    rules: &CellRules,
+    values: &S,
+    index: usize,
+    coordinate: &[usize],
+    seed: u64,
+    neighborhood: &mut Neighborhood,
+) -> Result<u32, Error> {
+    table.fill(values, index, coordinate, neighborhood);
+    let random = if rules.deterministic {
+        0
+    } else {
+        derive_seed(seed, index as u64)
+    };
+    match rules.of(index).next_state(neighborhood, random) {
+        Some(value) => Ok(value),
+        None => {
+            let cause = format!(
+                "no rule matches point {:?} with value {} and neighbors {:?}",
+                coordinate,
+                neighborhood.cell(),
+                neighborhood.neighbors()
+            );
+            Err(Error::Automaton(cause))
+        }
+    }
 }
 
 impl fmt::Display for Automaton {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "generation {}\ngrid:\n {}", self.gen, self.grid)
+        write!(
+            f,
+            "generation {}\ngrid:\n\n{}",
+            self.gen,
+            self.grid.render(&self.palette)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::automaton::automaton_builder::AutomatonBuilder;
+    use crate::automaton::neighborhood::NeighborhoodShape;
+    use crate::automaton::parsers::schemas::RulesSchema;
+    use crate::automaton::rules::{ElementaryRule, ExplicitRule, Rule, Unmatched};
+    use crate::automaton::test_configs::{config_rules, read_config};
+    use crate::utils::coordinates_iterator::CoordinatesIterator;
+    use ndarray::{ArrayD, IxDyn};
+
+    fn buffer_address(grid: &Grid) -> usize {
+        match grid.storage() {
+            Storage::Dense(cells) => dense_values(cells).as_ptr() as usize,
+            Storage::Packed(cells) => cells.words().as_ptr() as usize,
+        }
+    }
+
+    /// a grid with roughly a quarter of its cells set
+    fn scattered(dims: &[usize], seed: u32) -> Grid {
+        let mut grid = Grid::new(dims.to_vec(), ArrayD::zeros(IxDyn(dims)));
+        let mut x = seed;
+        for point in CoordinatesIterator::new(dims) {
+            x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            if (x >> 16) & 3 == 0 {
+                grid.set_point(&point[..], 1).unwrap();
+            }
+        }
+        grid
+    }
+
+    fn packed(grid: &Grid) -> Grid {
+        let mut packed = grid.clone();
+        packed.pack().unwrap();
+        packed
+    }
+
+    /// step the same grid packed and dense, and check they stay equal
+    fn assert_packed_matches_dense(grid: Grid, rules: Rules, generations: u32) {
+        let packed_grid = packed(&grid);
+        let mut packed = Automaton::new(packed_grid, rules.clone());
+        let mut dense = Automaton::new(grid, rules);
+        for _ in 0..generations {
+            packed.advance().unwrap();
+            dense.advance().unwrap();
+            assert_eq!(packed.grid().values(), dense.grid().values());
+        }
+    }
+
+    #[test]
+    fn advance_should_swap_between_two_buffers() {
+        let mut automaton = AutomatonBuilder::new(vec![5, 5])
+            .set_points(&[&[2, 1], &[2, 2], &[2, 3]])
+            .unwrap()
+            .set_rules(Rules::from_rulestring("B3/S23").unwrap())
+            .build()
+            .unwrap();
+        let start = automaton.grid().values();
+        let buffer = buffer_address(automaton.grid());
+        automaton.advance_multi(2).unwrap();
+        assert_eq!(automaton.grid().values(), start);
+        assert_eq!(buffer_address(automaton.grid()), buffer);
+    }
+
+    #[test]
+    fn advance_should_match_derived_neighborhoods() {
+        let rules = Rules::from_rulestring("B4/S345").unwrap();
+        let automaton = Automaton::new(scattered(&[9, 7, 5], 7), rules.clone());
+        assert_matches_derived(automaton, &rules, 3);
+    }
+
+    /// check every generation against derived neighborhoods
+    fn assert_matches_derived(mut automaton: Automaton, rules: &Rules, generations: u32) {
+        let dims = automaton.grid().dims().to_vec();
+        for _ in 0..generations {
+            let mut expected = automaton.grid().clone();
+            for point in CoordinatesIterator::new(&dims) {
+                let neighborhood = automaton
+                    .grid()
+                    .neighborhood(point.clone(), rules.shape())
+                    .unwrap();
+                if let Some(val) = rules.apply(&neighborhood) {
+                    expected.set_point(&point[..], val).unwrap();
+                }
+            }
+            automaton.advance().unwrap();
+            assert_eq!(automaton.grid().values(), expected.values());
+        }
+    }
+
+    #[test]
+    fn large_dense_grids_should_match_derived_neighborhoods() {
+        // more cells than a parallel chunk, with chunks starting mid-row
+        let brain = Rules::from_rulestring("/2/3").unwrap();
+        for dims in &[vec![70, 90], vec![17, 19, 23]] {
+            let automaton = Automaton::new(scattered(dims, 4), brain.clone());
+            assert!(!automaton.grid().is_packed());
+            assert!(automaton.grid().storage().len() > 4096);
+            assert_matches_derived(automaton, &brain, 3);
+        }
+    }
+
+    #[test]
+    fn large_packed_grids_should_match_derived_neighborhoods() {
+        // not Life-like, so stepped cell by cell on the packed grid
+        let mut rules = Rules::from_rulestring("B1/S12").unwrap();
+        rules.set_shape(NeighborhoodShape::VonNeumann(1));
+        let grid = packed(&scattered(&[50, 130], 6));
+        assert!(grid.storage().len() > 4096);
+        assert!(matches!(
+            Stepper::select(&grid, &rules, None).unwrap(),
+            Stepper::Cells { .. }
+        ));
+        assert_matches_derived(Automaton::new(grid, rules.clone()), &rules, 3);
+    }
+
+    #[test]
+    fn builder_should_pack_binary_grids() {
+        let automaton = AutomatonBuilder::new(vec![4, 4])
+            .set_rules(Rules::from_rulestring("B3/S23").unwrap())
+            .build()
+            .unwrap();
+        assert!(automaton.grid().is_packed());
+        let automaton = AutomatonBuilder::new(vec![4, 4])
+            .set_rules(Rules::from_rulestring("/2/3").unwrap())
+            .build()
+            .unwrap();
+        assert!(!automaton.grid().is_packed());
+    }
+
+    #[test]
+    fn packed_elementary_should_match_dense() {
+        let boundaries = [
+            Boundary::Periodic,
+            Boundary::Fixed(1),
+            Boundary::Reflecting,
+            Boundary::ZeroPadded,
+        ];
+        for width in &[1, 63, 64, 150] {
+            for boundary in &boundaries {
+                for number in 0..=255u8 {
+                    let mut grid = scattered(&[*width], u32::from(number));
+                    grid.set_boundaries(vec![*boundary]).unwrap();
+                    let rules = Rules::new(vec![Box::new(ElementaryRule::new(number))]);
+                    assert!(matches!(
+                        Stepper::select(&packed(&grid), &rules, None).unwrap(),
+                        Stepper::Elementary(n) if n == number
+                    ));
+                    assert_packed_matches_dense(grid, rules, 3);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn packed_life_like_should_match_dense() {
+        let boundaries = [
+            vec![Boundary::Periodic],
+            vec![Boundary::Fixed(1), Boundary::Reflecting],
+            vec![Boundary::ZeroPadded, Boundary::Fixed(1)],
+        ];
+        for dims in &[[1, 1], [7, 64], [12, 130]] {
+            for boundary in &boundaries {
+                for rulestring in &["B3/S23", "B36/S23", "B2/S", "B0/S8"] {
+                    let mut grid = scattered(&dims[..], 11);
+                    grid.set_boundaries(boundary.clone()).unwrap();
+                    let rules = Rules::from_rulestring(rulestring).unwrap();
+                    assert!(matches!(
+                        Stepper::select(&packed(&grid), &rules, None).unwrap(),
+                        Stepper::LifeLike { .. }
+                    ));
+                    assert_packed_matches_dense(grid, rules, 4);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn packed_cells_should_match_dense() {
+        // not Life-like, so stepped cell by cell on the packed grid
+        let mut grid = scattered(&[6, 70, 3], 5);
+        grid.set_boundaries(vec![Boundary::Reflecting]).unwrap();
+        let rules: Vec<Box<dyn Rule>> = ElementaryRule::new(30)
+            .to_explicit_rules()
+            .into_iter()
+            .map(|rule| Box::new(rule) as Box<dyn Rule>)
+            .collect();
+        let mut rules = Rules::new(rules);
+        rules.set_shape(crate::automaton::neighborhood::NeighborhoodShape::Custom(
+            vec![vec![0, -1, 0], vec![1, 1, 1]],
+        ));
+        assert!(matches!(
+            Stepper::select(&packed(&grid), &rules, None).unwrap(),
+            Stepper::Cells { .. }
+        ));
+        assert_packed_matches_dense(grid, rules, 4);
+    }
+
+    #[test]
+    fn packed_hybrid_should_match_dense() {
+        for width in &[1, 64, 150] {
+            for boundary in &[Boundary::Periodic, Boundary::Fixed(1), Boundary::ZeroPadded] {
+                let mut grid = scattered(&[*width], 7);
+                grid.set_boundaries(vec![*boundary]).unwrap();
+                let numbers: Vec<u8> = (0..*width)
+                    .map(|cell| [30, 90, 110, 150][cell % 7 % 4])
+                    .collect();
+                let map = RuleMap::from_elementary(&numbers);
+                let mut packed = Automaton::new(packed(&grid), Rules::new(Vec::new()));
+                packed.set_rule_map(map.clone()).unwrap();
+                let mut dense = Automaton::new(grid.clone(), Rules::new(Vec::new()));
+                dense.set_rule_map(map.clone()).unwrap();
+                assert!(matches!(
+                    Stepper::select(packed.grid(), &packed.rules, Some(&map)).unwrap(),
+                    Stepper::HybridElementary(_)
+                ));
+                for _ in 0..3 {
+                    let mut expected = dense.grid().clone();
+                    for (cell, number) in numbers.iter().enumerate() {
+                        let neighborhood = dense
+                            .grid()
+                            .neighborhood(vec![cell], &NeighborhoodShape::default())
+                            .unwrap();
+                        let next = ElementaryRule::new(*number).apply(&neighborhood).unwrap();
+                        expected.set_point(&[cell], next).unwrap();
+                    }
+                    packed.advance().unwrap();
+                    dense.advance().unwrap();
+                    assert_eq!(dense.grid().values(), expected.values());
+                    assert_eq!(packed.grid().values(), expected.values());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn rule_map_should_give_each_stripe_its_rules() {
+        // the top half runs Life and the bottom half Seeds
+        let mut map = RuleMap::new(vec![8, 8], Rules::from_rulestring("B3/S23").unwrap());
+        let seeds = map
+            .add_rules(Rules::from_rulestring("B2/S").unwrap())
+            .unwrap();
+        map.set_region(&[4, 0], &[7, 7], seeds).unwrap();
+        let mut automaton = AutomatonBuilder::new(vec![8, 8])
+            .set_points(&[&[1, 2], &[1, 3], &[1, 4], &[5, 2], &[5, 3]])
+            .unwrap()
+            .set_rule_map(map.clone())
+            .build()
+            .unwrap();
+        assert!(automaton.grid().is_packed());
+        for _ in 0..3 {
+            let mut expected = automaton.grid().clone();
+            for (point, _) in automaton.grid().iter() {
+                let index = point[0] * 8 + point[1];
+                let rules = map.rules_of(index);
+                let neighborhood = automaton
+                    .grid()
+                    .neighborhood(point.clone(), rules.shape())
+                    .unwrap();
+                expected
+                    .set_point(&point[..], rules.next_state(&neighborhood).unwrap())
+                    .unwrap();
+            }
+            automaton.advance().unwrap();
+            assert_eq!(automaton.grid().values(), expected.values());
+        }
+        assert!(AutomatonBuilder::new(vec![8, 9])
+            .set_rule_map(map)
+            .build()
+            .is_err());
+    }
+
+    fn percolation(seed: u64) -> Automaton {
+        let rules = config_rules("percolation/rules.json");
+        AutomatonBuilder::new(vec![200])
+            .set_points(&[&[98], &[99], &[100], &[101], &[102]])
+            .unwrap()
+            .set_rules(rules)
+            .set_seed(seed)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn stochastic_runs_should_follow_seed() {
+        let mut first = percolation(7);
+        assert!(first.grid().values().contains(&1));
+        let mut second = percolation(7);
+        let mut other = percolation(8);
+        first.advance_multi(40).unwrap();
+        second.advance_multi(40).unwrap();
+        other.advance_multi(40).unwrap();
+        assert_eq!(first.grid().values(), second.grid().values());
+        assert_ne!(first.grid().values(), other.grid().values());
+        assert!(matches!(
+            first.stepper,
+            Some(Stepper::Cells { ref rules, .. }) if !rules.rules[0].is_compiled()
+        ));
+        // a cell with no live neighbors dies, so the cluster stays within
+        // the light cone of the seed
+        for (point, value) in first.grid().iter() {
+            if value == 1 {
+                assert!((point[0] as i64 - 100).abs() <= 42);
+            }
+        }
+    }
+
+    #[test]
+    fn stochastic_second_order_should_retreat() {
+        let mut automaton = percolation(3);
+        automaton.set_second_order(true).unwrap();
+        let start = automaton.grid().values();
+        automaton.advance_multi(30).unwrap();
+        assert_ne!(automaton.grid().values(), start);
+        for _ in 0..30 {
+            automaton.retreat().unwrap();
+        }
+        assert_eq!(automaton.grid().values(), start);
+    }
+
+    /// one generation of rule 240 under a schedule
+    fn shift(schedule: Schedule) -> Vec<u32> {
+        let mut automaton = AutomatonBuilder::new(vec![5])
+            .set_points(&[&[1], &[2]])
+            .unwrap()
+            .set_elementary_rule(240)
+            .set_schedule(schedule)
+            .build()
+            .unwrap();
+        automaton.advance().unwrap();
+        automaton.grid().values()
+    }
+
+    #[test]
+    fn sweeps_should_see_earlier_updates() {
+        assert_eq!(shift(Schedule::Synchronous), vec![0, 0, 1, 1, 0]);
+        // from the left, every cell copies the already updated cell before it
+        assert_eq!(shift(Schedule::sweep(&[5])), vec![0, 0, 0, 0, 0]);
+        // from the right, every cell still sees the old value
+        assert_eq!(
+            shift(Schedule::Sweep(vec![4, 3, 2, 1, 0])),
+            vec![0, 0, 1, 1, 0]
+        );
+        assert_eq!(
+            shift(Schedule::BlockSequential(vec![vec![0, 1, 2, 3, 4]])),
+            vec![0, 0, 1, 1, 0]
+        );
+        assert_eq!(
+            shift(Schedule::BlockSequential(vec![vec![1, 3], vec![0, 2, 4]])),
+            vec![0, 0, 0, 1, 1]
+        );
+        assert_eq!(shift(Schedule::RandomIndependent(1.0)), vec![0, 0, 1, 1, 0]);
+        assert_eq!(shift(Schedule::RandomIndependent(0.0)), vec![0, 1, 1, 0, 0]);
+    }
+
+    #[test]
+    fn random_schedules_should_follow_seed() {
+        for schedule in &[Schedule::RandomSequential, Schedule::RandomIndependent(0.5)] {
+            let run = |seed: u64| {
+                let mut automaton = AutomatonBuilder::new(vec![16, 16])
+                    .set_points(&[&[7, 7], &[7, 8], &[7, 9], &[6, 9], &[5, 8], &[8, 8]])
+                    .unwrap()
+                    .set_rules(Rules::from_rulestring("B3/S23").unwrap())
+                    .set_schedule(schedule.clone())
+                    .set_seed(seed)
+                    .build()
+                    .unwrap();
+                automaton.advance_multi(5).unwrap();
+                automaton.grid().values()
+            };
+            assert_eq!(run(1), run(1));
+            assert_ne!(run(1), run(2));
+        }
+        // every cell is updated once by a random sequential schedule
+        let mut automaton = AutomatonBuilder::new(vec![40])
+            .set_elementary_rule(255)
+            .set_schedule(Schedule::RandomSequential)
+            .build()
+            .unwrap();
+        automaton.advance().unwrap();
+        assert_eq!(automaton.grid().values(), vec![1; 40]);
+    }
+
+    #[test]
+    fn second_order_should_need_synchronous_schedule() {
+        let mut builder = AutomatonBuilder::new(vec![5]);
+        builder
+            .set_elementary_rule(90)
+            .set_schedule(Schedule::RandomSequential)
+            .set_second_order();
+        assert!(matches!(builder.build(), Err(Error::SecondOrderSchedule)));
+        let mut automaton = builder.set_schedule(Schedule::Synchronous).build().unwrap();
+        assert!(automaton.set_schedule(Schedule::sweep(&[5])).is_err());
+        assert_eq!(automaton.schedule(), &Schedule::Synchronous);
+        automaton.set_second_order(false).unwrap();
+        automaton.set_schedule(Schedule::sweep(&[5])).unwrap();
+        assert!(automaton.set_second_order(true).is_err());
+        assert!(!automaton.is_second_order());
+    }
+
+    #[test]
+    fn row_major_sweeps_should_match_cells_updated_one_by_one() {
+        let dims = [6, 7];
+        let cells = dims.iter().product();
+        let life = Rules::from_rulestring("B3/S23").unwrap();
+        let mut sweep = Automaton::new(scattered(&dims, 5), life.clone());
+        sweep.set_schedule(Schedule::sweep(&dims)).unwrap();
+        let mut blocks = Automaton::new(scattered(&dims, 5), life);
+        blocks
+            .set_schedule(Schedule::BlockSequential(
+                (0..cells).map(|cell| vec![cell]).collect(),
+            ))
+            .unwrap();
+        for _ in 0..4 {
+            sweep.advance().unwrap();
+            blocks.advance().unwrap();
+            assert_eq!(sweep.grid().values(), blocks.grid().values());
+        }
+    }
+
+    #[test]
+    fn unmatched_policy_should_apply_to_cells_no_rule_matches() {
+        // only a live cell with no live neighbors has a rule
+        let mut rules = Rules::new(vec![Box::new(ExplicitRule::new(vec![0, 0], 1, 1))]);
+        let mut builder = AutomatonBuilder::new(vec![5]);
+        builder.set_points(&[&[2], &[3]]).unwrap();
+
+        let mut automaton = builder.set_rules(rules.clone()).build().unwrap();
+        automaton.advance().unwrap();
+        assert_eq!(automaton.grid().values(), vec![0, 0, 1, 1, 0]);
+
+        rules.set_unmatched(Unmatched::Default(1));
+        let mut automaton = builder.set_rules(rules.clone()).build().unwrap();
+        automaton.advance().unwrap();
+        assert_eq!(automaton.grid().values(), vec![1, 1, 1, 1, 1]);
+
+        rules.set_unmatched(Unmatched::Error);
+        let mut automaton = builder.set_rules(rules).build().unwrap();
+        let err = format!("{:?}", automaton.advance().unwrap_err());
+        assert!(err.contains("point [0] with value 0 and neighbors [0, 0]"));
+        assert_eq!(automaton.generation(), 0);
+    }
+
+    /// advance a second-order automaton and retreat it again
+    fn assert_reversible(mut automaton: Automaton, generations: u32) {
+        automaton.set_second_order(true).unwrap();
+        let start = automaton.grid().values();
+        automaton.advance_multi(generations).unwrap();
+        assert_ne!(automaton.grid().values(), start);
+        for _ in 0..generations {
+            automaton.retreat().unwrap();
+        }
+        assert_eq!(automaton.generation(), 0);
+        assert_eq!(automaton.grid().values(), start);
+        assert!(automaton
+            .previous()
+            .unwrap()
+            .values()
+            .iter()
+            .all(|v| *v == 0));
+    }
+
+    #[test]
+    fn second_order_should_retreat_to_start() {
+        let life = Rules::from_rulestring("B3/S23").unwrap();
+        assert_reversible(
+            Automaton::new(packed(&scattered(&[20, 70], 3)), life.clone()),
+            25,
+        );
+        assert_reversible(Automaton::new(scattered(&[20, 70], 3), life), 25);
+        let rule30 = Rules::new(vec![Box::new(ElementaryRule::new(30))]);
+        assert_reversible(Automaton::new(packed(&scattered(&[200], 9)), rule30), 40);
+        let mut brain = Automaton::new(
+            scattered(&[9, 9, 4], 1),
+            Rules::from_rulestring("/2/3").unwrap(),
+        );
+        brain.set_point_value(&[4, 4, 2], 2).unwrap();
+        assert_reversible(brain, 10);
+    }
+
+    #[test]
+    fn second_order_should_xor_with_previous_generation() {
+        let mut automaton = AutomatonBuilder::new(vec![7])
+            .set_point(&[3])
+            .unwrap()
+            .set_elementary_rule(90)
+            .set_second_order()
+            .build()
+            .unwrap();
+        // the generation before the first is all 0s, so rule 90 applies as is
+        automaton.advance().unwrap();
+        assert_eq!(automaton.grid().values(), vec![0, 0, 1, 0, 1, 0, 0]);
+        automaton.advance().unwrap();
+        assert_eq!(automaton.grid().values(), vec![0, 1, 0, 1, 0, 1, 0]);
+        assert_eq!(
+            automaton.previous().unwrap().values(),
+            vec![0, 0, 1, 0, 1, 0, 0]
+        );
+    }
+
+    #[test]
+    fn first_order_should_not_retreat() {
+        let mut automaton = AutomatonBuilder::new(vec![7])
+            .set_elementary_rule(90)
+            .build()
+            .unwrap();
+        automaton.advance().unwrap();
+        assert!(automaton.retreat().is_err());
+        automaton.set_second_order(true).unwrap();
+        assert!(automaton.retreat().is_err());
+        automaton.advance_multi(2).unwrap();
+        automaton.retreat().unwrap();
+        automaton.retreat().unwrap();
+        assert_eq!(automaton.generation(), 1);
+        assert!(automaton.retreat().is_err());
+        automaton.advance().unwrap();
+        let mut restored = Automaton::from_schema(&automaton.to_schema().unwrap()).unwrap();
+        restored.retreat().unwrap();
+        assert!(restored.retreat().is_err());
+    }
+
+    #[test]
+    fn totalistic_code_should_only_build_in_one_dimension() {
+        let rules = Rules::from_totalistic_code(777, 3, 1).unwrap();
+        assert!(AutomatonBuilder::new(vec![9])
+            .set_rules(rules.clone())
+            .build()
+            .is_ok());
+        assert!(AutomatonBuilder::new(vec![9, 9])
+            .set_rules(rules)
+            .build()
+            .is_err());
+    }
+
+    #[test]
+    fn fredkin_config_should_replicate() {
+        let rules = config_rules("fredkin/rules.json");
+        let mut automaton = AutomatonBuilder::new(vec![17, 17])
+            .set_point(&[8, 8])
+            .unwrap()
+            .set_rules(rules)
+            .build()
+            .unwrap();
+        automaton.advance_multi(4).unwrap();
+        let live: Vec<Vec<usize>> = automaton
+            .grid()
+            .iter()
+            .filter(|(_, value)| *value == 1)
+            .map(|(point, _)| point)
+            .collect();
+        assert_eq!(live, vec![vec![4, 8], vec![8, 4], vec![8, 12], vec![12, 8]]);
+    }
+
+    fn block_automaton(config: &str, dims: Vec<usize>, boundaries: Vec<Boundary>) -> Automaton {
+        let schema = read_config::<RulesSchema>(config);
+        let rule = BlockRule::from_schema(&schema.block_rule.unwrap(), 2, dims.len()).unwrap();
+        let mut builder = AutomatonBuilder::new(dims.clone());
+        builder.set_boundaries(boundaries).unwrap();
+        for (point, value) in scattered(&dims, 7).iter() {
+            builder.set_point_value(&point, value).unwrap();
+        }
+        builder.set_block_rule(rule).build().unwrap()
+    }
+
+    #[test]
+    fn critters_should_retreat_to_start() {
+        let mut automaton = block_automaton(
+            "critters/rules.json",
+            vec![12, 16],
+            vec![Boundary::Periodic],
+        );
+        let start = automaton.grid().values();
+        automaton.advance_multi(9).unwrap();
+        assert_ne!(automaton.grid().values(), start);
+        for _ in 0..9 {
+            automaton.retreat().unwrap();
+        }
+        assert_eq!(automaton.grid().values(), start);
+        assert!(automaton.retreat().is_err());
+    }
+
+    #[test]
+    fn sand_should_settle_without_losing_grains() {
+        let mut automaton = block_automaton(
+            "sand/rules.json",
+            vec![10, 8],
+            vec![Boundary::ZeroPadded, Boundary::Periodic],
+        );
+        let grains =
+            |automaton: &Automaton| automaton.grid().iter().filter(|(_, v)| *v == 1).count();
+        let start = grains(&automaton);
+        automaton.advance_multi(40).unwrap();
+        assert_eq!(grains(&automaton), start);
+        let settled = automaton.grid().clone();
+        automaton.advance_multi(2).unwrap();
+        assert_eq!(automaton.grid().values(), settled.values());
+        // every grain rests on the floor or on another grain
+        for (point, value) in settled.iter() {
+            if value == 1 && point[0] < 9 {
+                assert_eq!(
+                    settled.get_point_value(&[point[0] + 1, point[1]]).unwrap(),
+                    1
+                );
+            }
+        }
+        assert!(automaton.retreat().is_err());
+    }
+
+    #[test]
+    fn errors_should_tell_their_kind() {
+        let mut builder = AutomatonBuilder::new(vec![4, 4]);
+        assert!(matches!(
+            builder.set_point(&[4, 0]),
+            Err(Error::OutOfBounds { .. })
+        ));
+        assert!(matches!(
+            builder.set_point(&[1]),
+            Err(Error::DimensionMismatch {
+                expected: 2,
+                found: 1
+            })
+        ));
+        assert!(matches!(builder.build(), Err(Error::EmptyRules)));
+    }
+
+    #[test]
+    fn block_rules_should_fit_the_grid() {
+        let rule = BlockRule::new(2, 2).unwrap();
+        let mut builder = AutomatonBuilder::new(vec![4, 5]);
+        assert!(builder.set_block_rule(rule.clone()).build().is_err());
+        let mut automaton = AutomatonBuilder::new(vec![4, 4])
+            .set_block_rule(rule)
+            .build()
+            .unwrap();
+        automaton.set_schedule(Schedule::RandomSequential).unwrap();
+        assert!(automaton.advance().is_err());
+    }
+
+    /// check a restored copy goes on the same way
+    fn assert_resumes(mut automaton: Automaton, before: u32, after: u32) {
+        automaton.advance_multi(before).unwrap();
+        let saved = serde_json::to_string(&automaton.to_schema().unwrap()).unwrap();
+        let mut restored = Automaton::from_schema(&serde_json::from_str(&saved).unwrap()).unwrap();
+        assert_eq!(restored.generation(), before);
+        assert_eq!(restored.grid().is_packed(), automaton.grid().is_packed());
+        for _ in 0..after {
+            automaton.advance().unwrap();
+            restored.advance().unwrap();
+            assert_eq!(restored.grid().values(), automaton.grid().values());
+        }
+    }
+
+    #[test]
+    fn restored_state_should_resume_the_run() {
+        assert_resumes(percolation(7), 15, 25);
+        let mut second_order = percolation(3);
+        second_order.set_second_order(true).unwrap();
+        assert_resumes(second_order, 10, 10);
+        let mut brain = Automaton::new(
+            scattered(&[12, 12], 5),
+            Rules::from_rulestring("B2/S/C3").unwrap(),
+        );
+        brain.set_schedule(Schedule::RandomSequential).unwrap();
+        brain.set_seed(11);
+        assert_resumes(brain, 5, 5);
+        let mut totalistic = Automaton::new(
+            scattered(&[40], 2),
+            Rules::from_totalistic_code(777, 3, 1).unwrap(),
+        );
+        totalistic.set_point_value(&[20], 2).unwrap();
+        assert_resumes(totalistic, 6, 6);
+        let map = RuleMap::from_schema(&read_config("hybrid/rule_map.json"), &[16]).unwrap();
+        let hybrid = AutomatonBuilder::new(vec![16])
+            .set_points(&[&[3], &[7]])
+            .unwrap()
+            .set_rule_map(map)
+            .build()
+            .unwrap();
+        assert_resumes(hybrid, 4, 8);
+        let critters = block_automaton(
+            "critters/rules.json",
+            vec![12, 16],
+            vec![Boundary::Periodic],
+        );
+        assert_resumes(critters, 5, 6);
+    }
+
+    #[test]
+    fn saved_state_should_load_from_file() {
+        let mut automaton = block_automaton(
+            "sand/rules.json",
+            vec![10, 8],
+            vec![Boundary::ZeroPadded, Boundary::Periodic],
+        );
+        automaton.advance_multi(3).unwrap();
+        let path = std::env::temp_dir().join("cellular-automaton-save-test.json");
+        automaton.save(&path).unwrap();
+        let loaded = Automaton::load(&path);
+        fs::remove_file(&path).unwrap();
+        let loaded = loaded.unwrap();
+        assert_eq!(loaded.generation(), 3);
+        assert_eq!(loaded.grid().values(), automaton.grid().values());
+        assert_eq!(loaded.grid().boundaries(), automaton.grid().boundaries());
+        assert_eq!(loaded.block_rule(), automaton.block_rule());
+
+        let mut schema = automaton.to_schema().unwrap();
+        schema.version = SAVE_VERSION + 1;
+        assert!(Automaton::from_schema(&schema).is_err());
+        schema.version = SAVE_VERSION;
+        schema.cells.pop();
+        assert!(Automaton::from_schema(&schema).is_err());
     }
 }
@@ -1,6 +1,7 @@
-use crate::automaton::grid::Grid;
-use convert_base::Convert;
-use exitfailure::ExitFailure;
+use crate::automaton::boundary::{Boundary, Position};
+use crate::automaton::grid::{CellStorage, Grid};
+use crate::automaton::parsers::schemas::NeighborhoodSchema;
+use crate::error::Error;
 
 pub struct Neighborhood {
     neighbors: Vec<u32>,
@@ -8,23 +9,12 @@ pub struct Neighborhood {
 }
 
 impl Neighborhood {
-    pub fn derive(grid: &Grid, point: &Vec<usize>) -> Result<Self, ExitFailure> {
-        let mut converter = Convert::new(10, 3);
+    pub fn derive(grid: &Grid, point: &[usize], shape: &NeighborhoodShape) -> Result<Self, Error> {
         let dims = grid.dims();
-        let num_of_dims = dims.len();
-        let base: u32 = 3;
-        let range = base.pow(num_of_dims as u32);
+        let cell = grid.get_point_value(point)?;
         let mut neighbors: Vec<u32> = Vec::new();
-        let mut cell: u32 = 0;
-        for num in 0..range {
-            let mut offset = converter.convert::<u32, u32>(&vec![num]);
-            let offset = pad_to_n_and_adjust(&mut offset, num_of_dims);
-            let neighbor = add_points_on_toroid(point, &offset, &dims[..]);
-            if &neighbor == point {
-                cell = grid.get_point_value(&neighbor[..])?;
-            } else {
-                neighbors.push(grid.get_point_value(&neighbor[..])?)
-            }
+        for offset in shape.offsets(dims.len())? {
+            neighbors.push(grid.get_offset_value(point, &offset)?)
         }
         Ok(Self::new(neighbors, cell))
     }
@@ -33,46 +23,341 @@ impl Neighborhood {
         Self { neighbors, cell }
     }
 
-    pub fn neighbors(&self) -> Vec<u32> {
-        self.neighbors.clone()
+    /// neighborhood number index, read as digits in base states
+    pub fn from_index(index: usize, states: u32, neighbors: usize) -> Self {
+        let states = states as usize;
+        let mut values = vec![0; neighbors + 1];
+        let mut rest = index;
+        for value in values.iter_mut().rev() {
+            *value = (rest % states) as u32;
+            rest /= states;
+        }
+        let cell = values.remove(0);
+        Self::new(values, cell)
+    }
+
+    pub fn neighbors(&self) -> &[u32] {
+        &self.neighbors[..]
     }
     pub fn cell(&self) -> u32 {
         self.cell
     }
 }
 
-fn pad_to_n_and_adjust(vec: &mut Vec<u32>, n: usize) -> Vec<i32> {
-    let mut res_vec: Vec<i32> = Vec::new();
-    for i in 0..vec.len() {
-        res_vec.push(vec[i] as i32 - 1);
+/// neighborhood offsets computed once for a grid
+#[derive(Debug, Clone)]
+pub struct NeighborhoodTable {
+    dims: Vec<usize>,
+    boundaries: Vec<Boundary>,
+    offsets: Vec<Vec<i32>>,
+    /// linear index difference of each offset
+    deltas: Vec<isize>,
+    /// furthest any offset reaches along each axis
+    reach: Vec<usize>,
+    strides: Vec<usize>,
+}
+
+impl NeighborhoodTable {
+    pub fn new(grid: &Grid, shape: &NeighborhoodShape) -> Result<Self, Error> {
+        let dims = grid.dims().to_vec();
+        let offsets = shape.offsets(dims.len())?;
+        let mut strides = vec![1; dims.len()];
+        for axis in (0..dims.len().saturating_sub(1)).rev() {
+            strides[axis] = strides[axis + 1] * dims[axis + 1];
+        }
+        let deltas = offsets
+            .iter()
+            .map(|offset| {
+                offset
+                    .iter()
+                    .zip(&strides)
+                    .map(|(o, stride)| *o as isize * *stride as isize)
+                    .sum()
+            })
+            .collect();
+        let mut reach = vec![0; dims.len()];
+        for offset in &offsets {
+            for (r, o) in reach.iter_mut().zip(offset) {
+                *r = (*r).max(o.unsigned_abs() as usize);
+            }
+        }
+        Ok(Self {
+            dims,
+            boundaries: grid.boundaries().to_vec(),
+            offsets,
+            deltas,
+            reach,
+            strides,
+        })
+    }
+
+    /// number of neighbors of every cell
+    pub fn len(&self) -> usize {
+        self.offsets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.offsets.is_empty()
+    }
+
+    /// read the neighborhood of a cell into a buffer
+    pub fn fill<S: CellStorage + ?Sized>(
+        &self,
+        values: &S,
+        index: usize,
+        coordinate: &[usize],
+        neighborhood: &mut Neighborhood,
+    ) {
+        neighborhood.cell = values.get(index);
+        neighborhood.neighbors.clear();
+        let interior = coordinate
+            .iter()
+            .zip(&self.dims)
+            .zip(&self.reach)
+            .all(|((c, dim), reach)| *c >= *reach && *c + *reach < *dim);
+        if interior {
+            for delta in &self.deltas {
+                neighborhood
+                    .neighbors
+                    .push(values.get((index as isize + *delta) as usize));
+            }
+            return;
+        }
+        for offset in &self.offsets {
+            neighborhood
+                .neighbors
+                .push(self.edge_value(values, coordinate, offset));
+        }
     }
-    while res_vec.len() < n {
-        res_vec.push(-1);
+
+    /// value of a neighbor past an edge
+    fn edge_value<S: CellStorage + ?Sized>(
+        &self,
+        values: &S,
+        coordinate: &[usize],
+        offset: &[i32],
+    ) -> u32 {
+        let mut index = 0;
+        for axis in 0..self.dims.len() {
+            let target = coordinate[axis] as i64 + i64::from(offset[axis]);
+            match self.boundaries[axis].resolve(target, self.dims[axis]) {
+                Position::Inside(position) => index += position * self.strides[axis],
+                Position::Outside(value) => return value,
+            }
+        }
+        values.get(index)
     }
-    res_vec.reverse();
-    res_vec
 }
 
-fn add_points_on_toroid(point_a: &Vec<usize>, point_b: &Vec<i32>, dims: &[usize]) -> Vec<usize> {
-    // check that all sizes are equal
-    assert_eq!(point_a.len(), point_b.len());
-    assert_eq!(point_a.len(), dims.len());
+/// cells that make up the neighborhood of a cell
+#[derive(Debug, Clone, PartialEq)]
+pub enum NeighborhoodShape {
+    /// every cell within the given Chebyshev distance
+    Moore(u32),
+    /// every cell within the given Manhattan distance
+    VonNeumann(u32),
+    /// 6 neighbors of a hexagonal grid in axial coordinates (2 dimensions)
+    Hexagonal,
+    /// an explicit list of offsets
+    Custom(Vec<Vec<i32>>),
+}
+
+impl NeighborhoodShape {
+    /// offsets of the neighbors, in neighborhood order
+    pub fn offsets(&self, dimensions: usize) -> Result<Vec<Vec<i32>>, Error> {
+        match self {
+            NeighborhoodShape::Moore(radius) => box_offsets(dimensions, *radius, |_| true),
+            NeighborhoodShape::VonNeumann(radius) => box_offsets(dimensions, *radius, |offset| {
+                offset.iter().map(|o| o.unsigned_abs()).sum::<u32>() <= *radius
+            }),
+            NeighborhoodShape::Hexagonal => {
+                if dimensions != 2 {
+                    return Err(Error::Neighborhood(format!(
+                        "a hexagonal neighborhood needs 2 dimensions, got {}",
+                        dimensions
+                    )));
+                }
+                Ok(vec![
+                    vec![-1, 0],
+                    vec![-1, 1],
+                    vec![0, -1],
+                    vec![0, 1],
+                    vec![1, -1],
+                    vec![1, 0],
+                ])
+            }
+            NeighborhoodShape::Custom(offsets) => {
+                for offset in offsets {
+                    if offset.len() != dimensions || offset.iter().all(|o| *o == 0) {
+                        return Err(Error::Neighborhood(format!(
+                            "custom offset {:?} should be a non-zero offset of {} dimensions",
+                            offset, dimensions
+                        )));
+                    }
+                }
+                Ok(offsets.clone())
+            }
+        }
+    }
+
+    /// number of neighbors of a cell
+    pub fn size(&self, dimensions: usize) -> Result<usize, Error> {
+        Ok(self.offsets(dimensions)?.len())
+    }
+}
 
-    let mut zipped = vec![0; point_a.len()];
+impl Default for NeighborhoodShape {
+    fn default() -> Self {
+        NeighborhoodShape::Moore(1)
+    }
+}
 
-    // add two input arrays in zip
-    for (rref, val) in zipped
-        .iter_mut()
-        .zip(point_a.iter().zip(point_b).map(|(a, b)| *a as i32 + b))
-    {
-        *rref = val;
+impl From<&NeighborhoodSchema> for NeighborhoodShape {
+    fn from(schema: &NeighborhoodSchema) -> Self {
+        match schema {
+            NeighborhoodSchema::Moore { radius } => NeighborhoodShape::Moore(*radius),
+            NeighborhoodSchema::VonNeumann { radius } => NeighborhoodShape::VonNeumann(*radius),
+            NeighborhoodSchema::Hexagonal => NeighborhoodShape::Hexagonal,
+            NeighborhoodSchema::Custom { offsets } => NeighborhoodShape::Custom(offsets.clone()),
+        }
+    }
+}
+
+impl From<&NeighborhoodShape> for NeighborhoodSchema {
+    fn from(shape: &NeighborhoodShape) -> Self {
+        match shape {
+            NeighborhoodShape::Moore(radius) => NeighborhoodSchema::Moore { radius: *radius },
+            NeighborhoodShape::VonNeumann(radius) => {
+                NeighborhoodSchema::VonNeumann { radius: *radius }
+            }
+            NeighborhoodShape::Hexagonal => NeighborhoodSchema::Hexagonal,
+            NeighborhoodShape::Custom(offsets) => NeighborhoodSchema::Custom {
+                offsets: offsets.clone(),
+            },
+        }
+    }
+}
+
+/// offsets in [-radius, radius]^dimensions accepted by the filter
+fn box_offsets(
+    dimensions: usize,
+    radius: u32,
+    filter: impl Fn(&[i32]) -> bool,
+) -> Result<Vec<Vec<i32>>, Error> {
+    if radius == 0 {
+        return Err(Error::Neighborhood(
+            "neighborhood radius must be at least 1".to_string(),
+        ));
+    }
+    let radius = radius as i32;
+    let mut offsets = Vec::new();
+    let mut offset = vec![-radius; dimensions];
+    loop {
+        if offset.iter().any(|o| *o != 0) && filter(&offset) {
+            offsets.push(offset.clone());
+        }
+        // advance the last axis first, carrying into the ones before it
+        let mut axis = dimensions;
+        loop {
+            if axis == 0 {
+                return Ok(offsets);
+            }
+            axis -= 1;
+            if offset[axis] < radius {
+                offset[axis] += 1;
+                break;
+            }
+            offset[axis] = -radius;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn moore_should_list_offsets_first_axis_slowest() {
+        let offsets = NeighborhoodShape::Moore(1).offsets(2).unwrap();
+        assert_eq!(
+            offsets,
+            vec![
+                vec![-1, -1],
+                vec![-1, 0],
+                vec![-1, 1],
+                vec![0, -1],
+                vec![0, 1],
+                vec![1, -1],
+                vec![1, 0],
+                vec![1, 1],
+            ]
+        );
+        assert_eq!(NeighborhoodShape::Moore(2).size(3).unwrap(), 124);
+    }
+
+    #[test]
+    fn von_neumann_should_keep_manhattan_ball() {
+        let offsets = NeighborhoodShape::VonNeumann(1).offsets(2).unwrap();
+        assert_eq!(
+            offsets,
+            vec![vec![-1, 0], vec![0, -1], vec![0, 1], vec![1, 0]]
+        );
+        assert_eq!(NeighborhoodShape::VonNeumann(2).size(2).unwrap(), 12);
+    }
+
+    #[test]
+    fn shapes_should_reject_wrong_dimensions() {
+        assert!(NeighborhoodShape::Hexagonal.offsets(3).is_err());
+        assert!(NeighborhoodShape::Custom(vec![vec![1]]).offsets(2).is_err());
+        assert!(NeighborhoodShape::Custom(vec![vec![0, 0]])
+            .offsets(2)
+            .is_err());
+        assert!(NeighborhoodShape::Moore(0).offsets(2).is_err());
+    }
+
+    #[test]
+    fn derive_should_follow_shape_order() {
+        let mut grid = Grid::new(vec![3, 3], ndarray::ArrayD::zeros(ndarray::IxDyn(&[3, 3])));
+        for i in 0..3 {
+            for j in 0..3 {
+                grid.set_point(&[i, j], (i * 3 + j) as u32).unwrap();
+            }
+        }
+        let shape = NeighborhoodShape::Custom(vec![vec![0, 1], vec![-1, -1], vec![1, 0]]);
+        let neighborhood = Neighborhood::derive(&grid, &[0, 0], &shape).unwrap();
+        assert_eq!(neighborhood.neighbors(), vec![1, 8, 3]);
+        assert_eq!(neighborhood.cell(), 0);
+    }
+
+    #[test]
+    fn table_should_agree_with_derive() {
+        let mut grid = Grid::new(vec![4, 5], ndarray::ArrayD::zeros(ndarray::IxDyn(&[4, 5])));
+        for i in 0..4 {
+            for j in 0..5 {
+                grid.set_point(&[i, j], (i * 5 + j) as u32).unwrap();
+            }
+        }
+        grid.set_boundaries(vec![Boundary::Reflecting, Boundary::Fixed(99)])
+            .unwrap();
+        let shape = NeighborhoodShape::Moore(2);
+        let table = NeighborhoodTable::new(&grid, &shape).unwrap();
+        let mut neighborhood = Neighborhood::new(Vec::new(), 0);
+        for (index, (point, _)) in grid.iter().enumerate() {
+            table.fill(grid.storage(), index, &point, &mut neighborhood);
+            let derived = Neighborhood::derive(&grid, &point, &shape).unwrap();
+            assert_eq!(neighborhood.neighbors(), derived.neighbors());
+            assert_eq!(neighborhood.cell(), derived.cell());
+        }
     }
 
-    let mut result: Vec<usize> = Vec::new();
-    // zip with dimension to mod result
-    for (rref, val) in zipped.iter_mut().zip(dims.iter()) {
-        let modulo = *val as i32;
-        result.push(((*rref + modulo) % modulo) as usize);
+    #[test]
+    fn derive_should_honor_boundaries() {
+        let mut grid = Grid::new(vec![2, 2], ndarray::ArrayD::zeros(ndarray::IxDyn(&[2, 2])));
+        grid.set_point(&[0, 0], 1).unwrap();
+        grid.set_boundaries(vec![Boundary::Fixed(2), Boundary::Reflecting])
+            .unwrap();
+        let shape = NeighborhoodShape::Custom(vec![vec![-1, 0], vec![0, -1], vec![1, 2]]);
+        let neighborhood = Neighborhood::derive(&grid, &[0, 0], &shape).unwrap();
+        assert_eq!(neighborhood.neighbors(), vec![2, 1, 0]);
     }
-    result
 }
@@ -0,0 +1,38 @@
+/// ANSI colors of the cell states
+#[derive(Debug, Clone)]
+pub struct Palette {
+    colors: Vec<u8>,
+}
+
+/// gray, green, yellow, red, blue, magenta, cyan, white
+const DEFAULT_COLORS: [u8; 8] = [90, 32, 33, 31, 34, 35, 36, 37];
+
+impl Palette {
+    pub fn new(colors: Vec<u8>) -> Self {
+        if colors.is_empty() {
+            return Self::default();
+        }
+        Self { colors }
+    }
+
+    pub fn colors(&self) -> &[u8] {
+        &self.colors
+    }
+
+    pub fn color(&self, state: u32) -> u8 {
+        self.colors[state as usize % self.colors.len()]
+    }
+
+    /// a single colored block for a cell in the given state
+    pub fn paint(&self, state: u32) -> String {
+        format!("\x1b[{}m▊\x1b[0m", self.color(state))
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self {
+            colors: DEFAULT_COLORS.to_vec(),
+        }
+    }
+}
@@ -0,0 +1,79 @@
+use crate::automaton::parsers::schemas::CoordinatesSchema;
+use crate::automaton::parsers::{check_planar, SyntaxError};
+use crate::error::Error;
+
+/// read the live cells of a plaintext (.cells) pattern
+pub fn parse_cells(text: &str) -> Result<CoordinatesSchema, SyntaxError> {
+    let mut coordinates = Vec::new();
+    let rows = text
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.starts_with('!'));
+    for (row, (number, line)) in rows.enumerate() {
+        for (column, c) in line.trim_end().chars().enumerate() {
+            match c {
+                'O' | '*' => coordinates.push(vec![row, column]),
+                '.' => {}
+                _ => {
+                    let cause = format!("unexpected {:?}, cells are . or O", c);
+                    return Err((number + 1, column + 1, cause));
+                }
+            }
+        }
+    }
+    Ok(CoordinatesSchema { coordinates })
+}
+
+/// write live cells as a plaintext pattern
+pub fn write_cells(coordinates: &[Vec<usize>]) -> Result<String, Error> {
+    check_planar(coordinates)?;
+    let height = coordinates.iter().map(|point| point[0] + 1).max();
+    let mut rows = vec![Vec::new(); height.unwrap_or(0)];
+    for point in coordinates {
+        rows[point[0]].push(point[1]);
+    }
+    let mut out = String::new();
+    for columns in rows {
+        let width = columns.iter().max().map_or(1, |column| column + 1);
+        let mut row = vec!['.'; width];
+        for column in columns {
+            row[column] = 'O';
+        }
+        out.extend(row);
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::automaton::parsers::{parse_coordinates, parse_file_to_schema};
+    use crate::automaton::test_configs::config_path;
+
+    #[test]
+    fn cells_should_match_json_coordinates() {
+        let config = config_path("game_of_life");
+        let mut json =
+            parse_file_to_schema::<CoordinatesSchema>(&config.join("coordinates_pulsar.json"))
+                .unwrap()
+                .coordinates;
+        json.sort_unstable();
+        let cells = parse_coordinates(&config.join("pulsar.cells")).unwrap();
+        assert_eq!(cells.coordinates, json);
+        let written = write_cells(&cells.coordinates).unwrap();
+        assert_eq!(parse_cells(&written).unwrap().coordinates, json);
+    }
+
+    #[test]
+    fn cells_should_skip_comments_and_reject_other_characters() {
+        let glider = parse_cells("!Name: Glider\n.O\n..O\nOOO\n").unwrap();
+        assert_eq!(
+            glider.coordinates,
+            vec![vec![0, 1], vec![1, 2], vec![2, 0], vec![2, 1], vec![2, 2]]
+        );
+        assert_eq!(write_cells(&glider.coordinates).unwrap(), ".O\n..O\nOOO\n");
+        assert_eq!(parse_cells("!\n.O\nO.x").unwrap_err().0, 3);
+        assert!(write_cells(&[vec![1, 2, 3]]).is_err());
+    }
+}
@@ -0,0 +1,91 @@
+use crate::automaton::parsers::schemas::CoordinatesSchema;
+use crate::automaton::parsers::{check_planar, SyntaxError};
+use crate::error::Error;
+
+/// first line of a Life 1.06 pattern
+pub const LIFE_106_HEADER: &str = "#Life 1.06";
+
+/// read the live cells of a Life 1.06 pattern, shifted to be non-negative
+pub fn parse_life106(text: &str) -> Result<CoordinatesSchema, SyntaxError> {
+    let mut lines = text
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty());
+    match lines.next() {
+        Some((_, header)) if header.trim() == LIFE_106_HEADER => {}
+        first => {
+            let number = first.map_or(0, |(number, _)| number);
+            let cause = format!("a Life 1.06 pattern starts with {:?}", LIFE_106_HEADER);
+            return Err((number + 1, 1, cause));
+        }
+    }
+    let mut cells = Vec::new();
+    for (number, line) in lines.filter(|(_, line)| !line.starts_with('#')) {
+        let values: Vec<i64> = line
+            .split_whitespace()
+            .map_while(|value| value.parse().ok())
+            .collect();
+        if values.len() != 2 || line.split_whitespace().count() != 2 {
+            let cause = format!("expected the x and y of a cell, got {:?}", line.trim());
+            return Err((number + 1, 1, cause));
+        }
+        cells.push((values[1], values[0]));
+    }
+    let top = cells.iter().map(|(row, _)| *row).min().unwrap_or(0).min(0);
+    let left = cells
+        .iter()
+        .map(|(_, column)| *column)
+        .min()
+        .unwrap_or(0)
+        .min(0);
+    let mut coordinates: Vec<Vec<usize>> = cells
+        .iter()
+        .map(|(row, column)| vec![(row - top) as usize, (column - left) as usize])
+        .collect();
+    coordinates.sort_unstable();
+    coordinates.dedup();
+    Ok(CoordinatesSchema { coordinates })
+}
+
+/// write live cells as a Life 1.06 pattern
+pub fn write_life106(coordinates: &[Vec<usize>]) -> Result<String, Error> {
+    check_planar(coordinates)?;
+    let mut out = format!("{}\n", LIFE_106_HEADER);
+    for point in coordinates {
+        out.push_str(&format!("{} {}\n", point[1], point[0]));
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::automaton::parsers::{parse_coordinates, parse_file_to_schema};
+    use crate::automaton::test_configs::config_path;
+
+    #[test]
+    fn life106_should_match_json_coordinates() {
+        let config = config_path("game_of_life");
+        let mut json =
+            parse_file_to_schema::<CoordinatesSchema>(&config.join("coordinates_hwss.json"))
+                .unwrap()
+                .coordinates;
+        json.sort_unstable();
+        let life = parse_coordinates(&config.join("hwss.lif")).unwrap();
+        assert_eq!(life.coordinates, json);
+        let written = write_life106(&life.coordinates).unwrap();
+        assert_eq!(parse_life106(&written).unwrap().coordinates, json);
+    }
+
+    #[test]
+    fn life106_should_shift_negative_coordinates() {
+        let glider = parse_life106("#Life 1.06\n#D Glider\n0 -1\n1 0\n-1 1\n0 1\n1 1\n").unwrap();
+        assert_eq!(
+            glider.coordinates,
+            vec![vec![0, 1], vec![1, 2], vec![2, 0], vec![2, 1], vec![2, 2]]
+        );
+        assert_eq!(parse_life106("#Life 1.05\n0 0\n").unwrap_err().0, 1);
+        assert_eq!(parse_life106("#Life 1.06\n0 0\n1 x\n").unwrap_err().0, 3);
+        assert_eq!(parse_life106("#Life 1.06\n0 0 0\n").unwrap_err().0, 2);
+    }
+}
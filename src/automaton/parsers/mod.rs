@@ -1,12 +1,213 @@
+pub mod cells;
+pub mod life106;
+pub mod rle;
 pub mod schemas;
 
-use exitfailure::ExitFailure;
+use crate::automaton::parsers::schemas::{CoordinatesSchema, IncludeSchema, ScenarioSchema};
+use crate::error::Error;
 use serde::de::DeserializeOwned;
+use std::convert::TryFrom;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-pub fn parse_file_to_schema<T: DeserializeOwned>(path: &PathBuf) -> Result<T, ExitFailure> {
-    let data = fs::read_to_string(path)?;
-    let schema: T = serde_json::from_str(&data)?;
+pub fn parse_file_to_schema<T: DeserializeOwned>(path: &PathBuf) -> Result<T, Error> {
+    let data = fs::read_to_string(path).map_err(|source| Error::Io {
+        path: path.clone(),
+        source,
+    })?;
+    let schema: T = serde_json::from_str(&data).map_err(|e| Error::Parse {
+        path: path.clone(),
+        line: e.line(),
+        column: e.column(),
+        cause: e.to_string(),
+    })?;
     Ok(schema)
 }
+
+/// line, column and cause of a syntax error
+pub type SyntaxError = (usize, usize, String);
+
+/// read a pattern file with the given parser
+pub fn read_pattern<T>(
+    path: &PathBuf,
+    parse: fn(&str) -> Result<T, SyntaxError>,
+) -> Result<T, Error> {
+    let text = fs::read_to_string(path).map_err(|source| Error::Io {
+        path: path.clone(),
+        source,
+    })?;
+    parse(&text).map_err(|(line, column, cause)| Error::Parse {
+        path: path.clone(),
+        line,
+        column,
+        cause,
+    })
+}
+
+/// check coordinates are two-dimensional
+pub fn check_planar(coordinates: &[Vec<usize>]) -> Result<(), Error> {
+    match coordinates.iter().find(|point| point.len() != 2) {
+        Some(point) => Err(Error::DimensionMismatch {
+            expected: 2,
+            found: point.len(),
+        }),
+        None => Ok(()),
+    }
+}
+
+/// starting cells of an automaton, as read from a file
+#[derive(Debug, Clone)]
+pub enum StartingCells {
+    /// an RLE pattern, which may have more than two states
+    Pattern(rle::RlePattern),
+    /// coordinates of live cells
+    Coordinates(CoordinatesSchema),
+}
+
+/// read starting cells from an .rle, .cells, .lif or JSON file
+pub fn parse_starting_cells(path: &PathBuf) -> Result<StartingCells, Error> {
+    Ok(
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("rle") => StartingCells::Pattern(rle::RlePattern::read(path)?),
+            Some("cells") => StartingCells::Coordinates(read_pattern(path, cells::parse_cells)?),
+            Some("lif") | Some("life") => {
+                StartingCells::Coordinates(read_pattern(path, life106::parse_life106)?)
+            }
+            _ => StartingCells::Coordinates(parse_file_to_schema(path)?),
+        },
+    )
+}
+
+/// read starting coordinates from a file
+pub fn parse_coordinates(path: &PathBuf) -> Result<CoordinatesSchema, Error> {
+    match parse_starting_cells(path)? {
+        StartingCells::Pattern(pattern) => CoordinatesSchema::try_from(&pattern),
+        StartingCells::Coordinates(coordinates) => Ok(coordinates),
+    }
+}
+
+/// read a scenario and the files it includes
+pub fn parse_scenario(path: &PathBuf) -> Result<ScenarioSchema, Error> {
+    let mut scenario = parse_file_to_schema::<ScenarioSchema>(path)?;
+    let base = path.parent().unwrap_or_else(|| Path::new(""));
+    if let Some(rules) = scenario.rules.take() {
+        scenario.rules = Some(resolve_include(rules, base)?);
+    }
+    if let Some(rule_map) = scenario.rule_map.take() {
+        scenario.rule_map = Some(resolve_include(rule_map, base)?);
+    }
+    Ok(scenario)
+}
+
+/// the part itself, read from its file if it is included
+fn resolve_include<T: DeserializeOwned>(
+    part: IncludeSchema<T>,
+    base: &Path,
+) -> Result<IncludeSchema<T>, Error> {
+    match part {
+        IncludeSchema::Include { include } => Ok(IncludeSchema::Inline(parse_file_to_schema(
+            &base.join(include),
+        )?)),
+        inline => Ok(inline),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::automaton::automaton_builder::AutomatonBuilder;
+    use crate::automaton::parsers::schemas::DimensionsSchema;
+    use crate::automaton::test_configs::config_path;
+
+    #[test]
+    fn parse_errors_should_give_path_and_line() {
+        let path = config_path("rule110/coordinates.json");
+        match parse_file_to_schema::<DimensionsSchema>(&path) {
+            Err(Error::Parse {
+                path: error_path,
+                line,
+                ..
+            }) => {
+                assert_eq!(error_path, path);
+                assert_eq!(line, 3);
+            }
+            _ => panic!("coordinates should not parse as dimensions"),
+        }
+        let missing = path.with_file_name("missing.json");
+        assert!(matches!(
+            parse_file_to_schema::<DimensionsSchema>(&missing),
+            Err(Error::Io { .. })
+        ));
+    }
+
+    #[test]
+    fn coordinates_should_be_read_from_rle() {
+        let path = config_path("gosper_gun/gosper_gun.rle");
+        let coordinates = parse_coordinates(&path).unwrap().coordinates;
+        assert_eq!(coordinates.len(), 36);
+        assert_eq!(coordinates[0], vec![0, 24]);
+    }
+
+    #[test]
+    fn scenarios_should_read_included_rules() {
+        let path = config_path("scenarios/glider.json");
+        let unresolved = parse_file_to_schema::<ScenarioSchema>(&path).unwrap();
+        assert!(matches!(
+            unresolved.rules,
+            Some(IncludeSchema::Include { .. })
+        ));
+        assert!(AutomatonBuilder::from_scenario(&unresolved).is_err());
+
+        let scenario = parse_scenario(&path).unwrap();
+        let mut automaton = AutomatonBuilder::from_scenario(&scenario)
+            .unwrap()
+            .build()
+            .unwrap();
+        automaton.advance_multi(4).unwrap();
+        // a glider moves one cell down and one to the right every 4 generations
+        let live: Vec<Vec<usize>> = automaton
+            .grid()
+            .iter()
+            .filter(|(_, value)| *value == 1)
+            .map(|(point, _)| point)
+            .collect();
+        let moved: Vec<Vec<usize>> = scenario
+            .coordinates
+            .iter()
+            .map(|point| vec![point[0] + 1, point[1] + 1])
+            .collect();
+        assert_eq!(live, moved);
+    }
+
+    #[test]
+    fn scenarios_should_report_errors_in_inline_rules() {
+        let err = serde_json::from_str::<ScenarioSchema>(
+            r#"{ "dimensions": [5], "rules": { "states": "three" } }"#,
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(err.contains("invalid type: string \"three\""), "{}", err);
+        let err = serde_json::from_str::<ScenarioSchema>(
+            r#"{ "dimensions": [5], "rules": { "include": 3 } }"#,
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(err.contains("invalid type: integer `3`"), "{}", err);
+    }
+
+    #[test]
+    fn scenarios_should_not_give_both_rules_and_rule_map() {
+        let scenario: ScenarioSchema = serde_json::from_str(
+            r#"{
+                "dimensions": [5],
+                "rules": { "wolfram": 90 },
+                "rule_map": { "rules": [{ "wolfram": 90 }], "cells": [0, 0, 0, 0, 0] }
+            }"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            AutomatonBuilder::from_scenario(&scenario),
+            Err(Error::Build(_))
+        ));
+    }
+}
@@ -0,0 +1,512 @@
+use crate::automaton::grid::Grid;
+use crate::automaton::parsers::schemas::CoordinatesSchema;
+use crate::automaton::parsers::{read_pattern, SyntaxError};
+use crate::automaton::rules::Rules;
+use crate::error::Error;
+use std::convert::TryFrom;
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// largest state an RLE pattern can hold, written "yO"
+pub const MAX_RLE_STATE: u32 = 255;
+
+/// longest line of the body of a written pattern
+const MAX_LINE_LENGTH: usize = 70;
+
+/// two-dimensional RLE pattern, with rows down the first axis
+#[derive(Debug, Clone, PartialEq)]
+pub struct RlePattern {
+    /// number of rows, the `y` of the header
+    pub height: usize,
+    /// number of columns, the `x` of the header
+    pub width: usize,
+    /// the `rule` of the header, as a rulestring
+    pub rule: Option<String>,
+    /// row, column and state of every cell that is not 0
+    pub cells: Vec<([usize; 2], u32)>,
+}
+
+impl RlePattern {
+    /// the pattern of a two-dimensional grid, spanning all of it
+    pub fn from_grid(grid: &Grid, rule: Option<String>) -> Result<Self, Error> {
+        check_dimensions(grid)?;
+        let mut cells = Vec::new();
+        for (point, state) in grid.iter().filter(|(_, state)| *state != 0) {
+            if state > MAX_RLE_STATE {
+                let cause = format!(
+                    "point {:?} has state {} but RLE holds states up to {}",
+                    point, state, MAX_RLE_STATE
+                );
+                return Err(Error::Pattern(cause));
+            }
+            cells.push(([point[0], point[1]], state));
+        }
+        Ok(Self {
+            height: grid.dims()[0],
+            width: grid.dims()[1],
+            rule,
+            cells,
+        })
+    }
+
+    /// set the cells of the pattern in a grid at an offset
+    pub fn place(&self, grid: &mut Grid, offset: &[usize]) -> Result<(), Error> {
+        check_dimensions(grid)?;
+        if offset.len() != 2 {
+            return Err(Error::DimensionMismatch {
+                expected: 2,
+                found: offset.len(),
+            });
+        }
+        // nothing is written unless the whole pattern fits
+        let dims = grid.dims();
+        if offset[0] + self.height > dims[0] || offset[1] + self.width > dims[1] {
+            return Err(Error::OutOfBounds {
+                point: vec![
+                    offset[0] + self.height.saturating_sub(1),
+                    offset[1] + self.width.saturating_sub(1),
+                ],
+                dims: dims.to_vec(),
+            });
+        }
+        if grid.is_packed() && self.cells.iter().any(|(_, state)| *state > 1) {
+            return Err(Error::Grid(
+                "a packed grid only holds 0 and 1, but the pattern has more states".to_string(),
+            ));
+        }
+        for ([row, column], state) in &self.cells {
+            grid.set_point(&[offset[0] + row, offset[1] + column], *state)?;
+        }
+        Ok(())
+    }
+
+    /// the rule of the header, without the grid Golly adds after a colon
+    pub fn rulestring(&self) -> Option<&str> {
+        self.rule
+            .as_deref()
+            .map(|rule| rule.split(':').next().unwrap_or("").trim())
+    }
+
+    /// check the rule of the header, if any, is the given rules
+    pub fn check_rules(&self, rules: &Rules) -> Result<(), Error> {
+        let rulestring = match self.rulestring() {
+            Some(rulestring) => rulestring,
+            None => return Ok(()),
+        };
+        let own = Rules::from_rulestring(rulestring)?;
+        let same = match (own.as_life_like(), rules.as_life_like()) {
+            (Some(own), Some(other)) => own == other,
+            (None, None) => match (own.to_schema(), rules.to_schema()) {
+                (Ok(own), Ok(other)) => {
+                    serde_json::to_string(&own).ok() == serde_json::to_string(&other).ok()
+                }
+                _ => false,
+            },
+            _ => false,
+        };
+        if !same {
+            let cause = format!(
+                "the pattern is for rule {}, not the rules given",
+                rulestring
+            );
+            return Err(Error::Pattern(cause));
+        }
+        Ok(())
+    }
+
+    /// read a pattern from an RLE file
+    pub fn read(path: &PathBuf) -> Result<Self, Error> {
+        read_pattern(path, parse)
+    }
+
+    /// write the pattern to an RLE file
+    pub fn write(&self, path: &PathBuf) -> Result<(), Error> {
+        fs::write(path, self.to_string()).map_err(|source| Error::Io {
+            path: path.clone(),
+            source,
+        })
+    }
+}
+
+/// the live cells of a two-state pattern
+impl TryFrom<&RlePattern> for CoordinatesSchema {
+    type Error = Error;
+
+    fn try_from(pattern: &RlePattern) -> Result<Self, Self::Error> {
+        let mut coordinates = Vec::with_capacity(pattern.cells.len());
+        for ([row, column], state) in &pattern.cells {
+            if *state > 1 {
+                let cause = format!(
+                    "point {:?} has state {}, but coordinates only mark live cells",
+                    [row, column],
+                    state
+                );
+                return Err(Error::Pattern(cause));
+            }
+            coordinates.push(vec![*row, *column]);
+        }
+        Ok(CoordinatesSchema { coordinates })
+    }
+}
+
+impl FromStr for RlePattern {
+    type Err = Error;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        parse(text).map_err(|(line, column, cause)| {
+            Error::Pattern(format!("line {}, column {}: {}", line, column, cause))
+        })
+    }
+}
+
+impl fmt::Display for RlePattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "x = {}, y = {}", self.width, self.height)?;
+        if let Some(rule) = &self.rule {
+            write!(f, ", rule = {}", rule)?;
+        }
+        writeln!(f)?;
+        let multi_state = self.cells.iter().any(|(_, state)| *state > 1);
+        let mut cells = self.cells.clone();
+        cells.sort_unstable();
+        cells.dedup_by_key(|(point, _)| *point);
+        // runs of the same state along a row, as (row, first column,
+        // length, state)
+        let mut runs: Vec<(usize, usize, usize, u32)> = Vec::new();
+        for ([row, column], state) in cells {
+            match runs.last_mut() {
+                Some(run) if run.0 == row && run.1 + run.2 == column && run.3 == state => {
+                    run.2 += 1
+                }
+                _ => runs.push((row, column, 1, state)),
+            }
+        }
+        let mut tokens = Vec::new();
+        let (mut row, mut column) = (0, 0);
+        for (run_row, run_column, length, state) in runs {
+            if run_row > row {
+                tokens.push(token(run_row - row, "$"));
+                row = run_row;
+                column = 0;
+            }
+            if run_column > column {
+                tokens.push(token(run_column - column, &tag(0, multi_state)));
+            }
+            tokens.push(token(length, &tag(state, multi_state)));
+            column = run_column + length;
+        }
+        tokens.push("!".to_string());
+        let mut line = String::new();
+        for token in tokens {
+            if !line.is_empty() && line.len() + token.len() > MAX_LINE_LENGTH {
+                writeln!(f, "{}", line)?;
+                line.clear();
+            }
+            line.push_str(&token);
+        }
+        writeln!(f, "{}", line)
+    }
+}
+
+fn check_dimensions(grid: &Grid) -> Result<(), Error> {
+    if grid.dims().len() != 2 {
+        return Err(Error::DimensionMismatch {
+            expected: 2,
+            found: grid.dims().len(),
+        });
+    }
+    Ok(())
+}
+
+/// a run of `length` cells of a tag, the length left out if it is 1
+fn token(length: usize, tag: &str) -> String {
+    if length == 1 {
+        tag.to_string()
+    } else {
+        format!("{}{}", length, tag)
+    }
+}
+
+/// the letters a state is written as
+fn tag(state: u32, multi_state: bool) -> String {
+    match (state, multi_state) {
+        (0, false) => "b".to_string(),
+        (_, false) => "o".to_string(),
+        (0, true) => ".".to_string(),
+        (state, true) => {
+            let (prefix, letter) = ((state - 1) / 24, (state - 1) % 24);
+            let letter = char::from(b'A' + letter as u8);
+            match prefix {
+                0 => letter.to_string(),
+                prefix => format!("{}{}", char::from(b'p' + prefix as u8 - 1), letter),
+            }
+        }
+    }
+}
+
+/// read a pattern from RLE text
+fn parse(text: &str) -> Result<RlePattern, SyntaxError> {
+    let mut lines = text
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.trim_start().starts_with('#'));
+    let (number, header) = lines
+        .next()
+        .ok_or_else(|| (1, 1, "missing the \"x = ..., y = ...\" header".to_string()))?;
+    let mut pattern = parse_header(header).map_err(|cause| (number + 1, 1, cause))?;
+    let (mut row, mut column) = (0, 0);
+    let mut length: Option<usize> = None;
+    let mut prefix: Option<u32> = None;
+    for (number, line) in lines {
+        for (index, c) in line.char_indices() {
+            let error = |cause: String| (number + 1, index + 1, cause);
+            if prefix.is_some() && !c.is_ascii_uppercase() {
+                return Err(error(format!(
+                    "expected a letter after a prefix, got {:?}",
+                    c
+                )));
+            }
+            let state = match c {
+                '0'..='9' => {
+                    let digit = c as usize - '0' as usize;
+                    length = length
+                        .unwrap_or(0)
+                        .checked_mul(10)
+                        .and_then(|length| length.checked_add(digit));
+                    if length.is_none() {
+                        return Err(error("run length is too large".to_string()));
+                    }
+                    continue;
+                }
+                ' ' | '\t' => continue,
+                '!' => return Ok(pattern),
+                '$' => {
+                    row += length.take().unwrap_or(1);
+                    column = 0;
+                    continue;
+                }
+                'p'..='y' => {
+                    prefix = Some(c as u32 - 'p' as u32 + 1);
+                    continue;
+                }
+                'b' | '.' => 0,
+                'o' => 1,
+                'A'..='X' => prefix.take().unwrap_or(0) * 24 + (c as u32 - 'A' as u32) + 1,
+                _ => return Err(error(format!("unexpected {:?}", c))),
+            };
+            if state > MAX_RLE_STATE {
+                return Err(error(format!("state {} is above {}", state, MAX_RLE_STATE)));
+            }
+            let end = column + length.take().unwrap_or(1);
+            if end > pattern.width || (state != 0 && row >= pattern.height) {
+                let cause = format!(
+                    "cells reach past the {} by {} pattern the header declares",
+                    pattern.width, pattern.height
+                );
+                return Err(error(cause));
+            }
+            if state != 0 {
+                pattern
+                    .cells
+                    .extend((column..end).map(|column| ([row, column], state)));
+            }
+            column = end;
+        }
+    }
+    Ok(pattern)
+}
+
+/// read the header of an RLE file
+fn parse_header(header: &str) -> Result<RlePattern, String> {
+    let (mut width, mut height, mut rule) = (None, None, None);
+    let mut rest = header;
+    loop {
+        let (field, next) = match rest.find(',') {
+            Some(comma) => (&rest[..comma], Some(&rest[comma + 1..])),
+            None => (rest, None),
+        };
+        let mut parts = field.splitn(2, '=');
+        let key = parts.next().unwrap_or("").trim();
+        let value = parts
+            .next()
+            .ok_or_else(|| format!("header field {:?} should be key = value", field.trim()))?
+            .trim();
+        if key == "rule" {
+            // the rule is the last field, and may hold commas of its own
+            rule = Some(
+                rest.split_once('=')
+                    .map_or("", |(_, rule)| rule)
+                    .trim()
+                    .to_string(),
+            );
+            break;
+        }
+        let size = || {
+            value
+                .parse::<usize>()
+                .map_err(|_| format!("{} should be a size, got {:?}", key, value))
+        };
+        match key {
+            "x" => width = Some(size()?),
+            "y" => height = Some(size()?),
+            _ => {}
+        }
+        match next {
+            Some(next) => rest = next,
+            None => break,
+        }
+    }
+    match (width, height) {
+        (Some(width), Some(height)) => Ok(RlePattern {
+            height,
+            width,
+            rule,
+            cells: Vec::new(),
+        }),
+        _ => Err("the header needs both x and y".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::automaton::test_configs::config_path;
+    use ndarray::{ArrayD, IxDyn};
+
+    #[test]
+    fn rle_should_read_header_and_runs() {
+        let pattern: RlePattern =
+            "#N Glider\n#C a comment\nx = 3, y = 3, rule = B3/S23\nbo$2bo$3o!"
+                .parse()
+                .unwrap();
+        assert_eq!((pattern.width, pattern.height), (3, 3));
+        assert_eq!(pattern.rule.as_deref(), Some("B3/S23"));
+        let torus: RlePattern = "x = 3, y = 3, rule = B3/S23:T40,30\nbo$2bo$3o!"
+            .parse()
+            .unwrap();
+        assert_eq!(torus.rule.as_deref(), Some("B3/S23:T40,30"));
+        assert_eq!((torus.width, torus.height), (3, 3));
+        let cells: Vec<[usize; 2]> = pattern.cells.iter().map(|(point, _)| *point).collect();
+        assert_eq!(cells, vec![[0, 1], [1, 2], [2, 0], [2, 1], [2, 2]]);
+
+        let gun = RlePattern::read(&config_path("gosper_gun/gosper_gun.rle")).unwrap();
+        assert_eq!((gun.width, gun.height), (36, 9));
+        assert_eq!(gun.cells.len(), 36);
+    }
+
+    #[test]
+    fn rle_should_read_multiple_states() {
+        let pattern: RlePattern = "x = 5, y = 2\n.A2B$\n3.pAyO!".parse().unwrap();
+        assert_eq!(
+            pattern.cells,
+            vec![
+                ([0, 1], 1),
+                ([0, 2], 2),
+                ([0, 3], 2),
+                ([1, 3], 25),
+                ([1, 4], 255)
+            ]
+        );
+    }
+
+    #[test]
+    fn rle_should_reject_malformed() {
+        for text in &[
+            "bo$2bo$3o!",
+            "x = 3\nbo!",
+            "x = 3, y = 3\nbo$4o!",
+            "x = 3, y = 3\nbo$$$o!",
+            "x = 3, y = 3\nbz!",
+            "x = 3, y = 3\npo!",
+        ] {
+            assert!(text.parse::<RlePattern>().is_err(), "{}", text);
+        }
+        match "x = 3, y = 3\nbo$\n2bz!".parse::<RlePattern>() {
+            Err(Error::Pattern(cause)) => assert!(cause.starts_with("line 3, column 3")),
+            _ => panic!("an unknown tag should be an error"),
+        }
+    }
+
+    #[test]
+    fn rle_should_round_trip_grids() {
+        let mut grid = Grid::new(vec![6, 80], ArrayD::zeros(IxDyn(&[6, 80])));
+        for column in 0..80 {
+            grid.set_point(&[1, column], u32::from(column % 3 == 0))
+                .unwrap();
+        }
+        grid.set_point(&[4, 7], 1).unwrap();
+        let written = RlePattern::from_grid(&grid, Some("B3/S23".to_string())).unwrap();
+        let text = written.to_string();
+        assert!(text.starts_with("x = 80, y = 6, rule = B3/S23\n$o2bo"));
+        assert!(text.lines().all(|line| line.len() <= MAX_LINE_LENGTH));
+        let read: RlePattern = text.parse().unwrap();
+        assert_eq!(read, written);
+        let mut placed = Grid::new(vec![6, 80], ArrayD::zeros(IxDyn(&[6, 80])));
+        read.place(&mut placed, &[0, 0]).unwrap();
+        assert_eq!(placed.values(), grid.values());
+
+        grid.set_point(&[5, 79], 30).unwrap();
+        let text = RlePattern::from_grid(&grid, None).unwrap().to_string();
+        assert!(text.starts_with("x = 80, y = 6\n$A2.A"));
+        assert!(text.trim_end().ends_with("pF!"));
+        let mut placed = Grid::new(vec![6, 80], ArrayD::zeros(IxDyn(&[6, 80])));
+        text.parse::<RlePattern>()
+            .unwrap()
+            .place(&mut placed, &[0, 0])
+            .unwrap();
+        assert_eq!(placed.values(), grid.values());
+        assert!(RlePattern::from_grid(&placed, None)
+            .unwrap()
+            .place(&mut placed, &[1, 0])
+            .is_err());
+        assert_eq!(placed.values(), grid.values());
+    }
+
+    #[test]
+    fn rle_should_give_coordinates_of_two_states_only() {
+        let glider: RlePattern = "x = 3, y = 3\nbo$2bo$3o!".parse().unwrap();
+        let coordinates = CoordinatesSchema::try_from(&glider).unwrap().coordinates;
+        assert_eq!(coordinates, vec![[0, 1], [1, 2], [2, 0], [2, 1], [2, 2]]);
+        let states: RlePattern = "x = 2, y = 1\nAB!".parse().unwrap();
+        assert!(matches!(
+            CoordinatesSchema::try_from(&states),
+            Err(Error::Pattern(_))
+        ));
+    }
+
+    #[test]
+    fn rle_should_check_its_rule() {
+        let mut pattern: RlePattern = "x = 1, y = 1, rule = B3/S23:T40,30\no!".parse().unwrap();
+        assert_eq!(pattern.rulestring(), Some("B3/S23"));
+        let life = Rules::from_rulestring("23/3").unwrap();
+        pattern.check_rules(&life).unwrap();
+        let highlife = Rules::from_rulestring("B36/S23").unwrap();
+        assert!(matches!(
+            pattern.check_rules(&highlife),
+            Err(Error::Pattern(_))
+        ));
+        pattern.rule = Some("/2/3".to_string());
+        pattern
+            .check_rules(&Rules::from_rulestring("B2/S/C3").unwrap())
+            .unwrap();
+        assert!(pattern.check_rules(&life).is_err());
+        pattern.rule = None;
+        pattern.check_rules(&highlife).unwrap();
+    }
+
+    #[test]
+    fn rle_should_not_place_partly() {
+        let pattern: RlePattern = "x = 3, y = 2\n3A$.B!".parse().unwrap();
+        let mut grid = Grid::new(vec![4, 4], ArrayD::zeros(IxDyn(&[4, 4])));
+        assert!(matches!(
+            pattern.place(&mut grid, &[2, 2]),
+            Err(Error::OutOfBounds { .. })
+        ));
+        assert!(grid.values().iter().all(|value| *value == 0));
+        grid.pack().unwrap();
+        assert!(pattern.place(&mut grid, &[0, 0]).is_err());
+        assert!(grid.values().iter().all(|value| *value == 0));
+    }
+}
@@ -1,4 +1,6 @@
-use serde::{Deserialize, Serialize};
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::path::PathBuf;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ExplicitRuleSchema {
@@ -7,10 +9,182 @@ pub struct ExplicitRuleSchema {
     pub next: u32,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct RulesSchema {
+    #[serde(default)]
+    pub rules: Vec<RuleSchema>,
+    #[serde(default)]
     pub explicit_rules: Vec<ExplicitRuleSchema>,
+    #[serde(default)]
     pub sum_rules: Vec<SumRuleSchema>,
+    #[serde(default)]
+    pub totalistic_rules: Vec<TotalisticRuleSchema>,
+    #[serde(default)]
+    pub rulestring: Option<String>,
+    #[serde(default)]
+    pub wolfram: Option<u8>,
+    #[serde(default)]
+    pub totalistic_code: Option<u64>,
+    #[serde(default)]
+    pub states: Option<u32>,
+    #[serde(default)]
+    pub palette: Option<Vec<u8>>,
+    #[serde(default)]
+    pub neighborhood: Option<NeighborhoodSchema>,
+    #[serde(default)]
+    pub unmatched: Option<UnmatchedSchema>,
+    #[serde(default)]
+    pub schedule: Option<ScheduleSchema>,
+    #[serde(default)]
+    pub block_rule: Option<BlockRuleSchema>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RuleSchema {
+    Elementary {
+        number: u8,
+    },
+    Explicit(ExplicitRuleSchema),
+    Sum(SumRuleSchema),
+    Generations {
+        birth: Vec<u32>,
+        survival: Vec<u32>,
+        states: u32,
+    },
+    Totalistic(TotalisticRuleSchema),
+    Stochastic {
+        rule: Box<RuleSchema>,
+        probability: f64,
+        #[serde(default)]
+        distribution: Option<Vec<f64>>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AutomatonSchema {
+    pub version: u32,
+    pub generation: u32,
+    #[serde(default)]
+    pub seed: u64,
+    #[serde(flatten)]
+    pub dimensions: DimensionsSchema,
+    pub cells: Vec<u32>,
+    #[serde(default)]
+    pub previous: Option<Vec<u32>>,
+    #[serde(default)]
+    pub second_order_since: u32,
+    pub rules: RulesSchema,
+    #[serde(default)]
+    pub rule_map: Option<RuleMapSchema>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ScenarioSchema {
+    #[serde(flatten)]
+    pub dimensions: DimensionsSchema,
+    #[serde(default)]
+    pub rules: Option<IncludeSchema<RulesSchema>>,
+    #[serde(default)]
+    pub rule_map: Option<IncludeSchema<RuleMapSchema>>,
+    #[serde(default)]
+    pub coordinates: Vec<Vec<usize>>,
+    #[serde(default)]
+    pub seed: Option<u64>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum IncludeSchema<T> {
+    Include { include: PathBuf },
+    Inline(T),
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for IncludeSchema<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = serde_json::Value::deserialize(deserializer)?;
+        match value.get("include") {
+            Some(include) => PathBuf::deserialize(include.clone())
+                .map(|include| IncludeSchema::Include { include })
+                .map_err(D::Error::custom),
+            None => T::deserialize(value)
+                .map(IncludeSchema::Inline)
+                .map_err(D::Error::custom),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RuleMapSchema {
+    pub rules: Vec<RulesSchema>,
+    #[serde(default)]
+    pub cells: Option<Vec<usize>>,
+    #[serde(default)]
+    pub regions: Vec<RegionSchema>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RegionSchema {
+    pub rules: usize,
+    pub from: Vec<usize>,
+    pub to: Vec<usize>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "snake_case")]
+pub enum UnmatchedSchema {
+    Keep,
+    Default(u32),
+    Error,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ScheduleSchema {
+    Synchronous,
+    RandomSequential,
+    Sweep {
+        #[serde(default)]
+        order: Option<Vec<Vec<usize>>>,
+    },
+    RandomIndependent {
+        probability: f64,
+    },
+    BlockSequential {
+        blocks: Vec<Vec<Vec<usize>>>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BlockRuleSchema {
+    pub transitions: Vec<BlockTransitionSchema>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BlockTransitionSchema {
+    pub from: Vec<u32>,
+    pub to: Vec<u32>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "shape", rename_all = "snake_case")]
+pub enum NeighborhoodSchema {
+    Moore {
+        #[serde(default = "default_radius")]
+        radius: u32,
+    },
+    VonNeumann {
+        #[serde(default = "default_radius")]
+        radius: u32,
+    },
+    Hexagonal,
+    Custom {
+        offsets: Vec<Vec<i32>>,
+    },
+}
+
+fn default_radius() -> u32 {
+    1
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -21,6 +195,26 @@ pub struct SumRuleSchema {
     pub next: u32,
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TotalisticRuleSchema {
+    pub condition: SumConditionSchema,
+    #[serde(default)]
+    pub weights: Option<Vec<u32>>,
+    #[serde(default)]
+    pub center: u32,
+    #[serde(default)]
+    pub current: Option<u32>,
+    pub next: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "snake_case")]
+pub enum SumConditionSchema {
+    Range(u32, u32),
+    Set(Vec<u32>),
+    Modulo { modulus: u32, remainder: u32 },
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct SumRulesSchema {
     pub rules: Vec<SumRuleSchema>,
@@ -34,4 +228,15 @@ pub struct CoordinatesSchema {
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct DimensionsSchema {
     pub dimensions: Vec<usize>,
+    #[serde(default)]
+    pub boundary: Vec<BoundarySchema>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "snake_case")]
+pub enum BoundarySchema {
+    Periodic,
+    Fixed(u32),
+    Reflecting,
+    ZeroPadded,
 }
@@ -0,0 +1,445 @@
+use crate::automaton::bitgrid::BitGrid;
+use crate::automaton::grid::CellStorage;
+use crate::automaton::parsers::schemas::RuleMapSchema;
+use crate::automaton::rules::{ElementaryRule, Rules};
+use crate::error::Error;
+use crate::utils::{coordinate_of, index_of};
+use std::convert::TryFrom;
+
+/// longest hybrid searched by `max_length_90_150`
+pub const MAX_HYBRID_LENGTH: usize = 32;
+
+/// rules of every cell of a hybrid automaton
+#[derive(Clone)]
+pub struct RuleMap {
+    rules: Vec<Rules>,
+    dims: Vec<usize>,
+    /// index into `rules` of the rules of each cell, in row-major order
+    cells: Vec<usize>,
+}
+
+impl RuleMap {
+    /// a map of the given dimensions where every cell follows `rules`
+    pub fn new(dims: Vec<usize>, rules: Rules) -> Self {
+        Self {
+            cells: vec![0; dims.iter().product()],
+            rules: vec![rules],
+            dims,
+        }
+    }
+
+    /// a one-dimensional map with one cell per elementary rule number
+    pub fn from_elementary(numbers: &[u8]) -> Self {
+        let mut distinct: Vec<u8> = Vec::new();
+        let cells = numbers
+            .iter()
+            .map(
+                |number| match distinct.iter().position(|seen| seen == number) {
+                    Some(index) => index,
+                    None => {
+                        distinct.push(*number);
+                        distinct.len() - 1
+                    }
+                },
+            )
+            .collect();
+        Self {
+            rules: distinct
+                .iter()
+                .map(|number| Rules::new(vec![Box::new(ElementaryRule::new(*number))]))
+                .collect(),
+            dims: vec![numbers.len()],
+            cells,
+        }
+    }
+
+    pub fn rules(&self) -> &[Rules] {
+        &self.rules[..]
+    }
+
+    pub fn dims(&self) -> &[usize] {
+        &self.dims[..]
+    }
+
+    /// index into `rules` of the rules of each cell, in row-major order
+    pub fn cells(&self) -> &[usize] {
+        &self.cells[..]
+    }
+
+    /// the rules of the cell at a linear index
+    pub fn rules_of(&self, index: usize) -> &Rules {
+        &self.rules[self.cells[index]]
+    }
+
+    /// add a rule set cells can follow, returning its index
+    pub fn add_rules(&mut self, rules: Rules) -> Result<usize, Error> {
+        let first = &self.rules[0];
+        if rules.states() != first.states() {
+            let cause = format!(
+                "every rule set must have {} states, got {}",
+                first.states(),
+                rules.states()
+            );
+            return Err(Error::RuleMap(cause));
+        }
+        if rules.shape() != first.shape() {
+            let cause = format!(
+                "every rule set must have a {:?} neighborhood, got {:?}",
+                first.shape(),
+                rules.shape()
+            );
+            return Err(Error::RuleMap(cause));
+        }
+        self.rules.push(rules);
+        Ok(self.rules.len() - 1)
+    }
+
+    /// make a cell follow the rule set of the given index
+    pub fn set_cell(&mut self, point: &[usize], rules: usize) -> Result<&mut Self, Error> {
+        self.set_region(point, point, rules)
+    }
+
+    /// set the rules of a region
+    pub fn set_region(
+        &mut self,
+        from: &[usize],
+        to: &[usize],
+        rules: usize,
+    ) -> Result<&mut Self, Error> {
+        self.check_index(rules)?;
+        for corner in &[from, to] {
+            if corner.len() != self.dims.len() {
+                return Err(Error::DimensionMismatch {
+                    expected: self.dims.len(),
+                    found: corner.len(),
+                });
+            }
+            if index_of(corner, &self.dims).is_none() {
+                return Err(Error::OutOfBounds {
+                    point: corner.to_vec(),
+                    dims: self.dims.clone(),
+                });
+            }
+        }
+        for index in 0..self.cells.len() {
+            let inside = coordinate_of(index, &self.dims)
+                .iter()
+                .zip(from.iter().zip(to))
+                .all(|(coordinate, (from, to))| {
+                    coordinate >= from.min(to) && coordinate <= from.max(to)
+                });
+            if inside {
+                self.cells[index] = rules;
+            }
+        }
+        Ok(self)
+    }
+
+    /// build a map from its config
+    pub fn from_schema(schema: &RuleMapSchema, dims: &[usize]) -> Result<Self, Error> {
+        if let Some(index) = schema.rules.iter().skip(1).position(|rules| {
+            rules.schedule.is_some() || rules.block_rule.is_some() || rules.palette.is_some()
+        }) {
+            let cause = format!(
+                "rule set {} sets a schedule, block rule or palette, which only the first rule set can",
+                index + 1
+            );
+            return Err(Error::RuleMap(cause));
+        }
+        let mut rules = schema.rules.iter();
+        let first = match rules.next() {
+            Some(first) => Rules::try_from(first)?,
+            None => {
+                return Err(Error::RuleMap(
+                    "a rule map needs at least one rule set".to_string(),
+                ))
+            }
+        };
+        let mut map = RuleMap::new(dims.to_vec(), first);
+        for schema in rules {
+            map.add_rules(Rules::try_from(schema)?)?;
+        }
+        if let Some(cells) = &schema.cells {
+            if cells.len() != map.cells.len() {
+                let cause = format!(
+                    "expected the rules of {} cells, got {}",
+                    map.cells.len(),
+                    cells.len()
+                );
+                return Err(Error::RuleMap(cause));
+            }
+            for rules in cells {
+                map.check_index(*rules)?;
+            }
+            map.cells = cells.clone();
+        }
+        for region in &schema.regions {
+            map.set_region(&region.from, &region.to, region.rules)?;
+        }
+        Ok(map)
+    }
+
+    /// the configuration of the map, listing the rule set of every cell
+    pub fn to_schema(&self) -> Result<RuleMapSchema, Error> {
+        Ok(RuleMapSchema {
+            rules: self
+                .rules
+                .iter()
+                .map(Rules::to_schema)
+                .collect::<Result<_, _>>()?,
+            cells: Some(self.cells.clone()),
+            regions: Vec::new(),
+        })
+    }
+
+    /// elementary rule number and cell mask of every rule set, if any
+    pub fn as_elementary(&self) -> Option<Vec<(u8, BitGrid)>> {
+        self.rules
+            .iter()
+            .enumerate()
+            .map(|(index, rules)| {
+                let number = rules.as_elementary()?;
+                let mut mask = BitGrid::new(&self.dims);
+                for (cell, rules) in self.cells.iter().enumerate() {
+                    if *rules == index {
+                        mask.set(cell, 1);
+                    }
+                }
+                Some((number, mask))
+            })
+            .collect()
+    }
+
+    fn check_index(&self, rules: usize) -> Result<(), Error> {
+        if rules >= self.rules.len() {
+            let cause = format!("no rule set {}, only {} are given", rules, self.rules.len());
+            return Err(Error::RuleMap(cause));
+        }
+        Ok(())
+    }
+}
+
+/// 90/150 rule numbers of a maximum length hybrid with null boundaries
+pub fn max_length_90_150(length: usize) -> Result<Vec<u8>, Error> {
+    if length == 0 || length > MAX_HYBRID_LENGTH {
+        let cause = format!(
+            "maximum-length hybrids are searched for lengths 1 to {}, got {}",
+            MAX_HYBRID_LENGTH, length
+        );
+        return Err(Error::RuleMap(cause));
+    }
+    let period = (1u64 << length) - 1;
+    let factors = prime_factors(period);
+    for count in 0..=length {
+        // every set of `count` cells, as a bit mask, in increasing order
+        let mut cells: u64 = (1 << count) - 1;
+        while cells < 1 << length {
+            if is_primitive(characteristic_polynomial(cells, length), length, &factors) {
+                return Ok((0..length)
+                    .map(|cell| if (cells >> cell) & 1 == 1 { 150 } else { 90 })
+                    .collect());
+            }
+            if cells == 0 {
+                break;
+            }
+            // next mask with the same number of bits set
+            let lowest = cells & cells.wrapping_neg();
+            let ripple = cells + lowest;
+            cells = ripple | (((cells ^ ripple) >> 2) / lowest);
+        }
+    }
+    let cause = format!("no maximum-length hybrid of length {}", length);
+    Err(Error::RuleMap(cause))
+}
+
+/// characteristic polynomial of a null-boundary 90/150 hybrid
+fn characteristic_polynomial(cells: u64, length: usize) -> u64 {
+    let (mut before, mut last) = (0u64, 1u64);
+    for cell in 0..length {
+        let diagonal = if (cells >> cell) & 1 == 1 { last } else { 0 };
+        let next = (last << 1) ^ diagonal ^ before;
+        before = last;
+        last = next;
+    }
+    last
+}
+
+/// whether x has order 2^degree - 1 modulo the polynomial
+fn is_primitive(polynomial: u64, degree: usize, factors: &[u64]) -> bool {
+    let period = (1u64 << degree) - 1;
+    let x = reduce(2, polynomial, degree);
+    power(x, period, polynomial, degree) == 1
+        && factors
+            .iter()
+            .all(|factor| power(x, period / factor, polynomial, degree) != 1)
+}
+
+fn power(base: u64, exponent: u64, polynomial: u64, degree: usize) -> u64 {
+    let mut result = 1;
+    let mut base = base;
+    let mut exponent = exponent;
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            result = multiply(result, base, polynomial, degree);
+        }
+        base = multiply(base, base, polynomial, degree);
+        exponent >>= 1;
+    }
+    result
+}
+
+/// product of two polynomials modulo polynomial
+fn multiply(a: u64, b: u64, polynomial: u64, degree: usize) -> u64 {
+    let mut product = 0;
+    for bit in 0..degree {
+        if (b >> bit) & 1 == 1 {
+            product ^= a << bit;
+        }
+    }
+    reduce(product, polynomial, degree)
+}
+
+fn reduce(value: u64, polynomial: u64, degree: usize) -> u64 {
+    let mut value = value;
+    for bit in (degree..64).rev() {
+        if (value >> bit) & 1 == 1 {
+            value ^= polynomial << (bit - degree);
+        }
+    }
+    value
+}
+
+/// distinct prime factors, by trial division
+// `is_multiple_of` needs Rust 1.87
+#[allow(clippy::manual_is_multiple_of)]
+fn prime_factors(number: u64) -> Vec<u64> {
+    let mut factors = Vec::new();
+    let mut rest = number;
+    let mut factor = 2;
+    while factor * factor <= rest {
+        if rest % factor == 0 {
+            factors.push(factor);
+            while rest % factor == 0 {
+                rest /= factor;
+            }
+        }
+        factor += 1;
+    }
+    if rest > 1 {
+        factors.push(rest);
+    }
+    factors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::automaton::automaton_builder::AutomatonBuilder;
+    use crate::automaton::boundary::Boundary;
+    use crate::automaton::test_configs::read_config;
+
+    #[test]
+    fn regions_should_override_in_order() {
+        let schema: RuleMapSchema = serde_json::from_str(
+            r#"{
+                "rules": [{ "rulestring": "B3/S23" }, { "rulestring": "B36/S23" }, { "rulestring": "B2/S" }],
+                "regions": [
+                    { "rules": 1, "from": [0, 1], "to": [2, 1] },
+                    { "rules": 2, "from": [2, 2], "to": [1, 0] }
+                ]
+            }"#,
+        )
+        .unwrap();
+        let map = RuleMap::from_schema(&schema, &[3, 3]).unwrap();
+        assert_eq!(map.cells(), &[0, 1, 0, 2, 2, 2, 2, 2, 2]);
+    }
+
+    #[test]
+    fn only_the_first_rule_set_should_set_automaton_settings() {
+        let schema: RuleMapSchema = serde_json::from_str(
+            r#"{
+                "rules": [
+                    { "rulestring": "B3/S23", "palette": [10, 20] },
+                    { "rulestring": "B36/S23", "schedule": { "type": "random_sequential" } }
+                ]
+            }"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            RuleMap::from_schema(&schema, &[3, 3]),
+            Err(Error::RuleMap(_))
+        ));
+    }
+
+    #[test]
+    fn rule_sets_should_share_states_and_shape() {
+        let mut map = RuleMap::new(vec![4], Rules::from_rulestring("B3/S23").unwrap());
+        assert!(map
+            .add_rules(Rules::from_rulestring("/2/3").unwrap())
+            .is_err());
+        assert!(map.set_cell(&[1], 1).is_err());
+        assert!(map.set_cell(&[4], 0).is_err());
+    }
+
+    #[test]
+    fn from_elementary_should_share_rule_sets() {
+        let map = RuleMap::from_elementary(&[90, 150, 150, 90]);
+        assert_eq!(map.rules().len(), 2);
+        assert_eq!(map.cells(), &[0, 1, 1, 0]);
+        assert_eq!(map.rules_of(2).as_elementary(), Some(150));
+    }
+
+    #[test]
+    fn max_length_hybrids_should_have_full_period() {
+        assert_eq!(max_length_90_150(1).unwrap(), vec![150]);
+        for length in 2..=10 {
+            let numbers = max_length_90_150(length).unwrap();
+            let mut automaton = AutomatonBuilder::new(vec![length])
+                .set_boundaries(vec![Boundary::ZeroPadded])
+                .unwrap()
+                .set_point(&[0])
+                .unwrap()
+                .set_rule_map(RuleMap::from_elementary(&numbers))
+                .build()
+                .unwrap();
+            let start = automaton.grid().values();
+            let mut period = 0;
+            loop {
+                automaton.advance().unwrap();
+                period += 1;
+                if automaton.grid().values() == start {
+                    break;
+                }
+            }
+            assert_eq!(period, (1 << length) - 1, "{:?}", numbers);
+        }
+    }
+
+    #[test]
+    fn max_length_search_should_reach_32_cells() {
+        let numbers = max_length_90_150(32).unwrap();
+        let cells = numbers
+            .iter()
+            .enumerate()
+            .filter(|(_, number)| **number == 150)
+            .fold(0, |cells, (cell, _)| cells | 1 << cell);
+        let factors = prime_factors((1 << 32) - 1);
+        assert_eq!(factors, vec![3, 5, 17, 257, 65537]);
+        assert!(is_primitive(
+            characteristic_polynomial(cells, 32),
+            32,
+            &factors
+        ));
+        assert!(max_length_90_150(33).is_err());
+    }
+
+    #[test]
+    fn hybrid_config_should_parse() {
+        let schema = read_config::<RuleMapSchema>("hybrid/rule_map.json");
+        let map = RuleMap::from_schema(&schema, &[16]).unwrap();
+        let numbers: Vec<u8> = (0..16)
+            .map(|cell| map.rules_of(cell).as_elementary().unwrap())
+            .collect();
+        assert_eq!(numbers, max_length_90_150(16).unwrap());
+    }
+}
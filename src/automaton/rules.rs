@@ -1,16 +1,66 @@
-use crate::automaton::neighborhood::Neighborhood;
-use crate::automaton::parsers::schemas::{RulesSchema, SumRuleSchema};
+use crate::automaton::neighborhood::{Neighborhood, NeighborhoodShape};
+use crate::automaton::parsers::schemas::{
+    ExplicitRuleSchema, NeighborhoodSchema, RuleSchema, RulesSchema, SumConditionSchema,
+    SumRuleSchema, TotalisticRuleSchema, UnmatchedSchema,
+};
+use crate::error::Error;
+use crate::utils::split_mix::{derive_seed, SplitMix64};
 
 use dyn_clone::DynClone;
+use std::convert::TryFrom;
+use std::fmt;
+
+/// number of cell states when none is declared
+pub const DEFAULT_STATES: u32 = 2;
 
 #[derive(Clone)]
 pub struct Rules {
     rules: Vec<Box<dyn Rule>>,
+    states: u32,
+    shape: NeighborhoodShape,
+    unmatched: Unmatched,
+}
+
+/// what happens to a cell that no rule matches
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Unmatched {
+    /// the cell keeps its value
+    #[default]
+    Keep,
+    /// the cell takes the given state
+    Default(u32),
+    /// advancing the automaton fails
+    Error,
+}
+
+impl From<&UnmatchedSchema> for Unmatched {
+    fn from(schema: &UnmatchedSchema) -> Self {
+        match schema {
+            UnmatchedSchema::Keep => Unmatched::Keep,
+            UnmatchedSchema::Default(state) => Unmatched::Default(*state),
+            UnmatchedSchema::Error => Unmatched::Error,
+        }
+    }
+}
+
+impl From<&Unmatched> for UnmatchedSchema {
+    fn from(unmatched: &Unmatched) -> Self {
+        match unmatched {
+            Unmatched::Keep => UnmatchedSchema::Keep,
+            Unmatched::Default(state) => UnmatchedSchema::Default(*state),
+            Unmatched::Error => UnmatchedSchema::Error,
+        }
+    }
 }
 
 impl Rules {
     pub fn new(rules: Vec<Box<dyn Rule>>) -> Self {
-        Rules { rules }
+        Rules {
+            rules,
+            states: DEFAULT_STATES,
+            shape: NeighborhoodShape::default(),
+            unmatched: Unmatched::default(),
+        }
     }
 
     pub fn rules(self) -> Vec<Box<dyn Rule>> {
@@ -27,6 +77,32 @@ impl Rules {
         None
     }
 
+    /// apply with a random value split per rule
+    pub fn apply_random(&self, neighborhood: &Neighborhood, random: u64) -> Option<u32> {
+        self.rules.iter().enumerate().find_map(|(index, rule)| {
+            rule.apply_random(neighborhood, derive_seed(random, index as u64))
+        })
+    }
+
+    /// next value of a cell, following the unmatched policy
+    pub fn next_state(&self, neighborhood: &Neighborhood) -> Option<u32> {
+        self.or_unmatched(self.apply(neighborhood), neighborhood)
+    }
+
+    /// like `next_state`, given a random value drawn for the cell
+    pub fn next_state_random(&self, neighborhood: &Neighborhood, random: u64) -> Option<u32> {
+        self.or_unmatched(self.apply_random(neighborhood, random), neighborhood)
+    }
+
+    fn or_unmatched(&self, next: Option<u32>, neighborhood: &Neighborhood) -> Option<u32> {
+        match (next, self.unmatched) {
+            (Some(next), _) => Some(next),
+            (None, Unmatched::Keep) => Some(neighborhood.cell()),
+            (None, Unmatched::Default(state)) => Some(state),
+            (None, Unmatched::Error) => None,
+        }
+    }
+
     pub fn add(&mut self, rules: &mut Vec<Box<dyn Rule>>) {
         self.rules.append(rules);
     }
@@ -34,13 +110,473 @@ impl Rules {
     pub fn is_empty(&self) -> bool {
         self.rules.is_empty()
     }
+
+    /// whether every rule always gives a neighborhood the same value
+    pub fn is_deterministic(&self) -> bool {
+        self.rules.iter().all(|rule| rule.is_deterministic())
+    }
+
+    /// number of states a cell can take, 0 to states - 1
+    pub fn states(&self) -> u32 {
+        self.states
+    }
+
+    pub fn set_states(&mut self, states: u32) -> &mut Self {
+        self.states = states;
+        self
+    }
+
+    /// the neighborhood every rule is matched against
+    pub fn shape(&self) -> &NeighborhoodShape {
+        &self.shape
+    }
+
+    pub fn set_shape(&mut self, shape: NeighborhoodShape) -> &mut Self {
+        self.shape = shape;
+        self
+    }
+
+    /// what happens to a cell that no rule matches
+    pub fn unmatched(&self) -> Unmatched {
+        self.unmatched
+    }
+
+    pub fn set_unmatched(&mut self, unmatched: Unmatched) -> &mut Self {
+        self.unmatched = unmatched;
+        self
+    }
+
+    /// check rules against the neighborhood size
+    pub fn check_shape(&self, dimensions: usize) -> Result<(), Error> {
+        let size = self.shape.size(dimensions)?;
+        for rule in &self.rules[..] {
+            match rule.neighborhood_size() {
+                Some(expected) if expected != size => {
+                    return Err(Error::Rules(format!(
+                        "rule expects {} neighbors but a {:?} neighborhood in {} dimensions has {}",
+                        expected, self.shape, dimensions, size
+                    )))
+                }
+                _ => continue,
+            }
+        }
+        Ok(())
+    }
+
+    /// check states and rule values
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.states < 2 {
+            return Err(Error::Rules(format!(
+                "an automaton needs at least 2 states, got {}",
+                self.states
+            )));
+        }
+        for rule in &self.rules[..] {
+            if rule.max_next() >= self.states {
+                return Err(Error::Rules(format!(
+                    "rule produces state {} but only {} states are declared",
+                    rule.max_next(),
+                    self.states
+                )));
+            }
+        }
+        if let Unmatched::Default(state) = self.unmatched {
+            if state >= self.states {
+                return Err(Error::Rules(format!(
+                    "unmatched cells default to state {} but only {} states are declared",
+                    state, self.states
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// elementary rule number of these rules, if any
+    pub fn as_elementary(&self) -> Option<u8> {
+        if self.states != 2 || self.shape != NeighborhoodShape::Moore(1) || !self.is_deterministic()
+        {
+            return None;
+        }
+        let mut number = 0;
+        for pattern in 0..8 {
+            let neighborhood =
+                Neighborhood::new(vec![pattern >> 2, pattern & 1], (pattern >> 1) & 1);
+            let next = self.next_state(&neighborhood)?;
+            number |= (next as u8 & 1) << pattern;
+        }
+        Some(number)
+    }
+
+    /// birth and survival masks of these rules, if Life-like
+    pub fn as_life_like(&self) -> Option<(u16, u16)> {
+        if self.states != 2 || self.shape != NeighborhoodShape::Moore(1) || !self.is_deterministic()
+        {
+            return None;
+        }
+        let mut masks = [0u16; 2];
+        let mut seen = [0u16; 2];
+        for cell in 0..2 {
+            for pattern in 0..256u32 {
+                let neighbors = (0..8).map(|bit| (pattern >> bit) & 1).collect();
+                let neighborhood = Neighborhood::new(neighbors, cell);
+                let next = self.next_state(&neighborhood)? as u16 & 1;
+                let live = pattern.count_ones();
+                let bit = 1 << live;
+                if seen[cell as usize] & bit == 0 {
+                    seen[cell as usize] |= bit;
+                    masks[cell as usize] |= next << live;
+                } else if (masks[cell as usize] >> live) & 1 != next {
+                    return None;
+                }
+            }
+        }
+        Some((masks[0], masks[1]))
+    }
+
+    /// check every neighborhood against every rule
+    pub fn analyze(&self, dimensions: usize) -> Result<RulesAnalysis, Error> {
+        let size = self.shape.size(dimensions)?;
+        let neighborhoods = (self.states as usize)
+            .checked_pow(size as u32 + 1)
+            .filter(|count| *count <= MAX_ANALYZED_NEIGHBORHOODS)
+            .ok_or_else(|| {
+                Error::Rules(format!(
+                    "too many neighborhoods to check: {} states with {} neighbors",
+                    self.states, size
+                ))
+            })?;
+        let mut analysis = RulesAnalysis {
+            neighborhoods,
+            conflicts: Vec::new(),
+            shadowed: Vec::new(),
+            never_match: Vec::new(),
+            uncovered: Vec::new(),
+        };
+        let mut fires = vec![false; self.rules.len()];
+        let mut matches_any = vec![false; self.rules.len()];
+        for index in 0..neighborhoods {
+            let neighborhood = Neighborhood::from_index(index, self.states, size);
+            let matches: Vec<(usize, u32)> = self
+                .rules
+                .iter()
+                .enumerate()
+                .filter_map(|(i, rule)| rule.apply(&neighborhood).map(|next| (i, next)))
+                .collect();
+            for (i, _) in &matches {
+                matches_any[*i] = true;
+            }
+            // a stochastic rule may not fire, leaving the cell to the
+            // rules after it, up to the first deterministic one. If no
+            // deterministic rule matches, the cell may be left unmatched.
+            let deterministic = matches
+                .iter()
+                .position(|(i, _)| self.rules[*i].is_deterministic());
+            let reached = deterministic.map_or(matches.len(), |last| last + 1);
+            for (i, _) in &matches[..reached] {
+                fires[*i] = true;
+            }
+            if deterministic.is_none() {
+                analysis
+                    .uncovered
+                    .push((neighborhood.neighbors().to_vec(), neighborhood.cell()));
+            }
+            for (i, (first, first_next)) in matches.iter().enumerate() {
+                if !self.rules[*first].is_deterministic() {
+                    continue;
+                }
+                for (second, second_next) in &matches[i + 1..] {
+                    let known = analysis
+                        .conflicts
+                        .iter()
+                        .any(|c| c.first == *first && c.second == *second);
+                    if first_next != second_next && !known {
+                        analysis.conflicts.push(Conflict {
+                            first: *first,
+                            second: *second,
+                            neighbors: neighborhood.neighbors().to_vec(),
+                            current: neighborhood.cell(),
+                            first_next: *first_next,
+                            second_next: *second_next,
+                        });
+                    }
+                }
+            }
+        }
+        analysis.shadowed = (0..fires.len())
+            .filter(|i| matches_any[*i] && !fires[*i])
+            .collect();
+        analysis.never_match = (0..fires.len()).filter(|i| !matches_any[*i]).collect();
+        Ok(analysis)
+    }
+
+    /// config of these rules
+    pub fn to_schema(&self) -> Result<RulesSchema, Error> {
+        let rules = self
+            .rules
+            .iter()
+            .map(|rule| {
+                rule.to_schema().ok_or_else(|| {
+                    Error::Rules("a rule cannot be written as a configuration".to_string())
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(RulesSchema {
+            rules,
+            states: Some(self.states),
+            neighborhood: Some(NeighborhoodSchema::from(&self.shape)),
+            unmatched: Some(UnmatchedSchema::from(&self.unmatched)),
+            ..RulesSchema::default()
+        })
+    }
+
+    /// build rules from a rulestring ("B3/S23", "B2/S/C3", "23/3" or "/2/3")
+    pub fn from_rulestring(rulestring: &str) -> Result<Self, Error> {
+        let parsed = parse_rulestring(rulestring)?;
+        if let Some(states) = parsed.states {
+            let mut rules = Rules::new(vec![Box::new(GenerationsRule::new(
+                parsed.birth,
+                parsed.survival,
+                states,
+            ))]);
+            rules.set_states(states);
+            return Ok(rules);
+        }
+        let mut rules: Vec<Box<dyn Rule>> = Vec::new();
+        for sum in parsed.birth {
+            rules.push(Box::new(SumEqualRule::new(SumRule::new(sum, 0, 1))));
+        }
+        for sum in 0..=MAX_RULESTRING_SUM {
+            let next = if parsed.survival.contains(&sum) { 1 } else { 0 };
+            rules.push(Box::new(SumEqualRule::new(SumRule::new(sum, 1, next))));
+        }
+        rules.push(Box::new(SumLargerRule::new(SumRule::new(
+            MAX_RULESTRING_SUM,
+            1,
+            0,
+        ))));
+        Ok(Rules::new(rules))
+    }
+
+    /// build a one-dimensional totalistic rule from its Wolfram code
+    pub fn from_totalistic_code(code: u64, states: u32, radius: u32) -> Result<Self, Error> {
+        if states < 2 || radius == 0 {
+            return Err(Error::Rules(format!(
+                "a totalistic code needs at least 2 states and a radius of at least 1, got {} and {}",
+                states, radius
+            )));
+        }
+        let max_sum = radius
+            .checked_mul(2)
+            .and_then(|width| (width + 1).checked_mul(states - 1))
+            .ok_or_else(|| {
+                Error::Rules(format!(
+                    "a totalistic code for {} states and radius {} has too many sums",
+                    states, radius
+                ))
+            })?;
+        let mut rules: Vec<Box<dyn Rule>> = Vec::new();
+        let mut rest = code;
+        for sum in 0..=max_sum {
+            let mut rule = TotalisticRule::new(
+                SumCondition::Set(vec![sum]),
+                (rest % u64::from(states)) as u32,
+            );
+            rule.set_weights(vec![1; 2 * radius as usize])
+                .set_center_weight(1);
+            rules.push(Box::new(rule));
+            rest /= u64::from(states);
+        }
+        if rest != 0 {
+            return Err(Error::Rules(format!(
+                "totalistic code {} is too large for {} states and radius {}",
+                code, states, radius
+            )));
+        }
+        let mut rules = Rules::new(rules);
+        rules
+            .set_states(states)
+            .set_shape(NeighborhoodShape::Moore(radius));
+        Ok(rules)
+    }
+}
+
+/// largest number of neighborhoods `Rules::analyze` tries
+const MAX_ANALYZED_NEIGHBORHOODS: usize = 1 << 22;
+
+/// what `Rules::analyze` found
+#[derive(Debug)]
+pub struct RulesAnalysis {
+    /// number of neighborhoods tried
+    pub neighborhoods: usize,
+    /// rules that give the same neighborhood different values
+    pub conflicts: Vec<Conflict>,
+    /// rules that an earlier rule always overrides
+    pub shadowed: Vec<usize>,
+    /// rules that match no neighborhood at all
+    pub never_match: Vec<usize>,
+    /// neighbors and current value of every neighborhood no rule matches
+    pub uncovered: Vec<(Vec<u32>, u32)>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Conflict {
+    pub first: usize,
+    pub second: usize,
+    pub neighbors: Vec<u32>,
+    pub current: u32,
+    pub first_next: u32,
+    pub second_next: u32,
+}
+
+/// uncovered neighborhoods listed in a report
+const LISTED_UNCOVERED: usize = 10;
+
+impl fmt::Display for RulesAnalysis {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "checked {} neighborhoods", self.neighborhoods)?;
+        writeln!(f, "\nconflicting rules: {}", self.conflicts.len())?;
+        for c in &self.conflicts {
+            writeln!(
+                f,
+                "  rules {} and {} give {} and {} for neighbors {:?} with current {}",
+                c.first, c.second, c.first_next, c.second_next, c.neighbors, c.current
+            )?;
+        }
+        writeln!(f, "\nrules that never fire: {}", self.shadowed.len())?;
+        if !self.shadowed.is_empty() {
+            writeln!(f, "  {:?}", self.shadowed)?;
+        }
+        writeln!(
+            f,
+            "\nrules that match no neighborhood: {}",
+            self.never_match.len()
+        )?;
+        if !self.never_match.is_empty() {
+            writeln!(f, "  {:?}", self.never_match)?;
+        }
+        writeln!(
+            f,
+            "\nneighborhoods no rule matches: {}",
+            self.uncovered.len()
+        )?;
+        for (neighbors, current) in self.uncovered.iter().take(LISTED_UNCOVERED) {
+            writeln!(f, "  neighbors {:?} with current {}", neighbors, current)?;
+        }
+        if self.uncovered.len() > LISTED_UNCOVERED {
+            writeln!(
+                f,
+                "  ... and {} more",
+                self.uncovered.len() - LISTED_UNCOVERED
+            )?;
+        }
+        Ok(())
+    }
 }
 
-impl From<&RulesSchema> for Rules {
-    fn from(schema: &RulesSchema) -> Self {
-        let mut rules = Vec::new();
+/// largest neighbor sum a Life-like rulestring can refer to
+const MAX_RULESTRING_SUM: u32 = 8;
+
+struct Rulestring {
+    birth: Vec<u32>,
+    survival: Vec<u32>,
+    states: Option<u32>,
+}
+
+/// split a rulestring into birth, survival and states
+fn parse_rulestring(rulestring: &str) -> Result<Rulestring, Error> {
+    let parts: Vec<&str> = rulestring.trim().split('/').collect();
+    if parts.len() != 2 && parts.len() != 3 {
+        return Err(Error::Rules(format!(
+            "rulestring {:?} should have the form B.../S... or B.../S.../C...",
+            rulestring
+        )));
+    }
+    let mut birth = None;
+    let mut survival = None;
+    let mut states = None;
+    for (i, part) in parts.iter().enumerate() {
+        let (section, digits) = match part.chars().next() {
+            Some('B') | Some('b') => ('B', &part[1..]),
+            Some('S') | Some('s') => ('S', &part[1..]),
+            Some('C') | Some('c') => ('C', &part[1..]),
+            // digits only: the legacy "survival/birth/states" order
+            _ => (['S', 'B', 'C'][i], *part),
+        };
+        let repeated = match section {
+            'B' => birth
+                .replace(parse_rulestring_digits(rulestring, digits)?)
+                .is_some(),
+            'S' => survival
+                .replace(parse_rulestring_digits(rulestring, digits)?)
+                .is_some(),
+            _ => states
+                .replace(parse_rulestring_states(rulestring, digits)?)
+                .is_some(),
+        };
+        if repeated {
+            return Err(Error::Rules(format!(
+                "rulestring {:?} repeats a section",
+                rulestring
+            )));
+        }
+    }
+    match (birth, survival) {
+        (Some(birth), Some(survival)) => Ok(Rulestring {
+            birth,
+            survival,
+            states,
+        }),
+        _ => Err(Error::Rules(format!(
+            "rulestring {:?} needs both a B and an S section",
+            rulestring
+        ))),
+    }
+}
+
+fn parse_rulestring_digits(rulestring: &str, digits: &str) -> Result<Vec<u32>, Error> {
+    let mut sums = Vec::new();
+    for c in digits.chars() {
+        match c.to_digit(10) {
+            Some(sum) if sum <= MAX_RULESTRING_SUM && !sums.contains(&sum) => sums.push(sum),
+            _ => {
+                return Err(Error::Rules(format!(
+                    "rulestring {:?} has an invalid neighbor count {:?}",
+                    rulestring, c
+                )))
+            }
+        }
+    }
+    Ok(sums)
+}
+
+fn parse_rulestring_states(rulestring: &str, digits: &str) -> Result<u32, Error> {
+    match digits.parse::<u32>() {
+        Ok(states) if states >= 2 => Ok(states),
+        _ => Err(Error::Rules(format!(
+            "rulestring {:?} has an invalid state count {:?}",
+            rulestring, digits
+        ))),
+    }
+}
+
+impl TryFrom<&RulesSchema> for Rules {
+    type Error = Error;
+
+    fn try_from(schema: &RulesSchema) -> Result<Self, Self::Error> {
+        let mut rules: Vec<Box<dyn Rule>> = Vec::new();
+        let mut states = DEFAULT_STATES;
+        for conf in &schema.rules[..] {
+            if let RuleSchema::Generations {
+                states: generations,
+                ..
+            } = conf
+            {
+                states = states.max(*generations);
+            }
+            rules.push(rule_from_schema(conf)?);
+        }
         for conf in &schema.sum_rules[..] {
-            rules.push(parse_rule_from_schema(conf));
+            rules.push(parse_rule_from_schema(conf)?);
         }
         for conf in &schema.explicit_rules[..] {
             rules.push(Box::new(ExplicitRule::new(
@@ -49,7 +585,42 @@ impl From<&RulesSchema> for Rules {
                 conf.next,
             )));
         }
-        Self { rules }
+        for conf in &schema.totalistic_rules[..] {
+            rules.push(Box::new(TotalisticRule::from(conf)));
+        }
+        if let Some(rulestring) = &schema.rulestring {
+            let from_rulestring = Rules::from_rulestring(rulestring)?;
+            states = states.max(from_rulestring.states);
+            rules.append(&mut from_rulestring.rules());
+        }
+        if let Some(number) = schema.wolfram {
+            rules.push(Box::new(ElementaryRule::new(number)));
+        }
+        if let Some(code) = schema.totalistic_code {
+            let radius = match &schema.neighborhood {
+                None => 1,
+                Some(NeighborhoodSchema::Moore { radius }) => *radius,
+                Some(_) => {
+                    return Err(Error::Rules(
+                        "a totalistic code needs a moore neighborhood".to_string(),
+                    ))
+                }
+            };
+            let from_code =
+                Rules::from_totalistic_code(code, schema.states.unwrap_or(DEFAULT_STATES), radius)?;
+            states = states.max(from_code.states);
+            rules.append(&mut from_code.rules());
+        }
+        let mut rules = Rules::new(rules);
+        rules.set_states(schema.states.unwrap_or(states));
+        if let Some(neighborhood) = &schema.neighborhood {
+            rules.set_shape(NeighborhoodShape::from(neighborhood));
+        }
+        if let Some(unmatched) = &schema.unmatched {
+            rules.set_unmatched(Unmatched::from(unmatched));
+        }
+        rules.validate()?;
+        Ok(rules)
     }
 }
 
@@ -59,19 +630,84 @@ impl From<&RulesSchema> for Rules {
 /// 1 : larger
 /// 2 : smaller
 ///
-
-pub fn parse_rule_from_schema(schema: &SumRuleSchema) -> Box<dyn Rule> {
+pub fn parse_rule_from_schema(schema: &SumRuleSchema) -> Result<Box<dyn Rule>, Error> {
     let sr = SumRule::new(schema.neighborhood, schema.current, schema.next);
     match schema.rule_type {
-        0 => return Box::new(SumEqualRule::new(sr)),
-        1 => return Box::new(SumLargerRule::new(sr)),
-        2 => return Box::new(SumSmallerRule::new(sr)),
-        _ => panic!("unsupported rule type!"),
+        0 => Ok(Box::new(SumEqualRule::new(sr))),
+        1 => Ok(Box::new(SumLargerRule::new(sr))),
+        2 => Ok(Box::new(SumSmallerRule::new(sr))),
+        rule_type => Err(Error::InvalidRuleType(rule_type)),
     }
 }
 
-pub trait Rule: DynClone {
+/// build a rule from its configuration in a list of rules
+pub fn rule_from_schema(schema: &RuleSchema) -> Result<Box<dyn Rule>, Error> {
+    Ok(match schema {
+        RuleSchema::Elementary { number } => Box::new(ElementaryRule::new(*number)),
+        RuleSchema::Explicit(conf) => Box::new(ExplicitRule::new(
+            conf.neighborhood.clone(),
+            conf.current,
+            conf.next,
+        )),
+        RuleSchema::Sum(conf) => parse_rule_from_schema(conf)?,
+        RuleSchema::Generations {
+            birth,
+            survival,
+            states,
+        } => {
+            if *states < 2 {
+                return Err(Error::Rules(format!(
+                    "a generations rule needs at least 2 states, got {}",
+                    states
+                )));
+            }
+            Box::new(GenerationsRule::new(
+                birth.clone(),
+                survival.clone(),
+                *states,
+            ))
+        }
+        RuleSchema::Totalistic(conf) => Box::new(TotalisticRule::from(conf)),
+        RuleSchema::Stochastic {
+            rule,
+            probability,
+            distribution,
+        } => {
+            let mut stochastic = StochasticRule::new(rule_from_schema(rule)?, *probability);
+            if let Some(weights) = distribution {
+                stochastic.set_distribution(weights.clone());
+            }
+            stochastic.check()?;
+            Box::new(stochastic)
+        }
+    })
+}
+
+pub trait Rule: DynClone + Send + Sync {
     fn apply(&self, _neighborhood: &Neighborhood) -> Option<u32>;
+
+    /// largest value this rule can give a cell
+    fn max_next(&self) -> u32;
+
+    /// number of neighbors the rule expects, if fixed
+    fn neighborhood_size(&self) -> Option<usize> {
+        None
+    }
+
+    /// apply with a random value, for rules that are not deterministic
+    fn apply_random(&self, neighborhood: &Neighborhood, _random: u64) -> Option<u32> {
+        self.apply(neighborhood)
+    }
+
+    /// whether the rule always gives a neighborhood the same value
+    fn is_deterministic(&self) -> bool {
+        true
+    }
+
+    /// the configuration of the rule, if it has one
+    fn to_schema(&self) -> Option<RuleSchema> {
+        None
+    }
 }
 
 dyn_clone::clone_trait_object!(Rule);
@@ -85,11 +721,27 @@ pub struct ExplicitRule {
 
 impl Rule for ExplicitRule {
     fn apply(&self, neighborhood: &Neighborhood) -> Option<u32> {
-        if &self.neighborhood == &neighborhood.neighbors() && self.current == neighborhood.cell() {
+        if self.neighborhood == neighborhood.neighbors() && self.current == neighborhood.cell() {
             return Some(self.next);
         }
         None
     }
+
+    fn max_next(&self) -> u32 {
+        self.next
+    }
+
+    fn neighborhood_size(&self) -> Option<usize> {
+        Some(self.neighborhood.len())
+    }
+
+    fn to_schema(&self) -> Option<RuleSchema> {
+        Some(RuleSchema::Explicit(ExplicitRuleSchema {
+            neighborhood: self.neighborhood.clone(),
+            current: self.current,
+            next: self.next,
+        }))
+    }
 }
 
 impl ExplicitRule {
@@ -102,6 +754,57 @@ impl ExplicitRule {
     }
 }
 
+/// Wolfram elementary rule
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ElementaryRule(pub u8);
+
+impl ElementaryRule {
+    pub fn new(number: u8) -> Self {
+        ElementaryRule(number)
+    }
+
+    pub fn number(&self) -> u8 {
+        self.0
+    }
+
+    /// expand into the equivalent 8 explicit rules
+    pub fn to_explicit_rules(&self) -> Vec<ExplicitRule> {
+        (0..8u32)
+            .map(|pattern| {
+                let (left, current, right) = ((pattern >> 2) & 1, (pattern >> 1) & 1, pattern & 1);
+                ExplicitRule::new(vec![left, right], current, self.next(left, current, right))
+            })
+            .collect()
+    }
+
+    fn next(&self, left: u32, current: u32, right: u32) -> u32 {
+        (u32::from(self.0) >> (left << 2 | current << 1 | right)) & 1
+    }
+}
+
+impl Rule for ElementaryRule {
+    fn apply(&self, neighborhood: &Neighborhood) -> Option<u32> {
+        let neighbors = neighborhood.neighbors();
+        let current = neighborhood.cell();
+        if neighbors.len() != 2 || neighbors.iter().any(|v| *v > 1) || current > 1 {
+            return None;
+        }
+        Some(self.next(neighbors[0], current, neighbors[1]))
+    }
+
+    fn max_next(&self) -> u32 {
+        1
+    }
+
+    fn neighborhood_size(&self) -> Option<usize> {
+        Some(2)
+    }
+
+    fn to_schema(&self) -> Option<RuleSchema> {
+        Some(RuleSchema::Elementary { number: self.0 })
+    }
+}
+
 #[derive(Clone)]
 pub struct SumRule {
     neighborhood: u32,
@@ -127,6 +830,16 @@ impl SumRule {
     pub fn next(&self) -> u32 {
         self.next
     }
+
+    /// the configuration of a sum rule of the given type
+    pub fn to_schema(&self, rule_type: u32) -> SumRuleSchema {
+        SumRuleSchema {
+            rule_type,
+            neighborhood: self.neighborhood,
+            current: self.current,
+            next: self.next,
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -144,6 +857,14 @@ impl Rule for SumEqualRule {
     fn apply(&self, _neighborhood: &Neighborhood) -> Option<u32> {
         apply_sum_rule_on_predicate(&self.rule, _neighborhood, &|a, b| a == b)
     }
+
+    fn max_next(&self) -> u32 {
+        self.rule.next()
+    }
+
+    fn to_schema(&self) -> Option<RuleSchema> {
+        Some(RuleSchema::Sum(self.rule.to_schema(0)))
+    }
 }
 
 #[derive(Clone)]
@@ -161,6 +882,14 @@ impl Rule for SumLargerRule {
     fn apply(&self, _neighborhood: &Neighborhood) -> Option<u32> {
         apply_sum_rule_on_predicate(&self.rule, _neighborhood, &|a, b| a > b)
     }
+
+    fn max_next(&self) -> u32 {
+        self.rule.next()
+    }
+
+    fn to_schema(&self) -> Option<RuleSchema> {
+        Some(RuleSchema::Sum(self.rule.to_schema(1)))
+    }
 }
 
 #[derive(Clone)]
@@ -178,6 +907,334 @@ impl Rule for SumSmallerRule {
     fn apply(&self, _neighborhood: &Neighborhood) -> Option<u32> {
         apply_sum_rule_on_predicate(&self.rule, _neighborhood, &|a, b| a < b)
     }
+
+    fn max_next(&self) -> u32 {
+        self.rule.next()
+    }
+
+    fn to_schema(&self) -> Option<RuleSchema> {
+        Some(RuleSchema::Sum(self.rule.to_schema(2)))
+    }
+}
+
+/// Generations rule: 0 is dead, 1 alive, the rest dying
+#[derive(Clone)]
+pub struct GenerationsRule {
+    birth: Vec<u32>,
+    survival: Vec<u32>,
+    states: u32,
+}
+
+impl GenerationsRule {
+    pub fn new(birth: Vec<u32>, survival: Vec<u32>, states: u32) -> Self {
+        Self {
+            birth,
+            survival,
+            states,
+        }
+    }
+}
+
+impl Rule for GenerationsRule {
+    fn apply(&self, neighborhood: &Neighborhood) -> Option<u32> {
+        let alive = neighborhood.neighbors().iter().filter(|v| **v == 1).count() as u32;
+        match neighborhood.cell() {
+            0 if self.birth.contains(&alive) => Some(1),
+            0 => Some(0),
+            1 if self.survival.contains(&alive) => Some(1),
+            cell if cell < self.states => Some((cell + 1) % self.states),
+            _ => None,
+        }
+    }
+
+    fn max_next(&self) -> u32 {
+        self.states - 1
+    }
+
+    fn to_schema(&self) -> Option<RuleSchema> {
+        Some(RuleSchema::Generations {
+            birth: self.birth.clone(),
+            survival: self.survival.clone(),
+            states: self.states,
+        })
+    }
+}
+
+/// sums a totalistic rule matches
+#[derive(Debug, Clone, PartialEq)]
+pub enum SumCondition {
+    /// every sum from the first value to the second, inclusive
+    Range(u32, u32),
+    /// any of the listed sums
+    Set(Vec<u32>),
+    /// every sum that leaves `remainder` when divided by `modulus`
+    Modulo { modulus: u32, remainder: u32 },
+}
+
+impl SumCondition {
+    pub fn matches(&self, sum: u32) -> bool {
+        match self {
+            SumCondition::Range(min, max) => *min <= sum && sum <= *max,
+            SumCondition::Set(sums) => sums.contains(&sum),
+            SumCondition::Modulo { modulus, remainder } => {
+                sum.checked_rem(*modulus) == Some(*remainder)
+            }
+        }
+    }
+}
+
+impl From<&SumConditionSchema> for SumCondition {
+    fn from(schema: &SumConditionSchema) -> Self {
+        match schema {
+            SumConditionSchema::Range(min, max) => SumCondition::Range(*min, *max),
+            SumConditionSchema::Set(sums) => SumCondition::Set(sums.clone()),
+            SumConditionSchema::Modulo { modulus, remainder } => SumCondition::Modulo {
+                modulus: *modulus,
+                remainder: *remainder,
+            },
+        }
+    }
+}
+
+impl From<&SumCondition> for SumConditionSchema {
+    fn from(condition: &SumCondition) -> Self {
+        match condition {
+            SumCondition::Range(min, max) => SumConditionSchema::Range(*min, *max),
+            SumCondition::Set(sums) => SumConditionSchema::Set(sums.clone()),
+            SumCondition::Modulo { modulus, remainder } => SumConditionSchema::Modulo {
+                modulus: *modulus,
+                remainder: *remainder,
+            },
+        }
+    }
+}
+
+/// totalistic rule on a weighted sum of the neighborhood
+#[derive(Clone)]
+pub struct TotalisticRule {
+    condition: SumCondition,
+    next: u32,
+    weights: Option<Vec<u32>>,
+    center_weight: u32,
+    current: Option<u32>,
+}
+
+impl TotalisticRule {
+    pub fn new(condition: SumCondition, next: u32) -> Self {
+        Self {
+            condition,
+            next,
+            weights: None,
+            center_weight: 0,
+            current: None,
+        }
+    }
+
+    /// weight of each neighbor, in the order of the neighborhood shape
+    pub fn set_weights(&mut self, weights: Vec<u32>) -> &mut Self {
+        self.weights = Some(weights);
+        self
+    }
+
+    /// weight of the cell itself in the sum
+    pub fn set_center_weight(&mut self, weight: u32) -> &mut Self {
+        self.center_weight = weight;
+        self
+    }
+
+    /// only match cells with the given value
+    pub fn set_current(&mut self, current: u32) -> &mut Self {
+        self.current = Some(current);
+        self
+    }
+
+    /// the weighted sum of a neighborhood, saturating at `u32::MAX`
+    pub fn sum(&self, neighborhood: &Neighborhood) -> u32 {
+        let neighbors = match &self.weights {
+            Some(weights) => neighborhood
+                .neighbors()
+                .iter()
+                .zip(weights)
+                .fold(0u32, |sum, (value, weight)| {
+                    sum.saturating_add(value.saturating_mul(*weight))
+                }),
+            None => neighborhood
+                .neighbors()
+                .iter()
+                .fold(0u32, |sum, value| sum.saturating_add(*value)),
+        };
+        neighbors.saturating_add(self.center_weight.saturating_mul(neighborhood.cell()))
+    }
+}
+
+impl Rule for TotalisticRule {
+    fn apply(&self, neighborhood: &Neighborhood) -> Option<u32> {
+        if matches!(self.current, Some(current) if current != neighborhood.cell()) {
+            return None;
+        }
+        if let Some(weights) = &self.weights {
+            if weights.len() != neighborhood.neighbors().len() {
+                return None;
+            }
+        }
+        if self.condition.matches(self.sum(neighborhood)) {
+            return Some(self.next);
+        }
+        None
+    }
+
+    fn max_next(&self) -> u32 {
+        self.next
+    }
+
+    fn neighborhood_size(&self) -> Option<usize> {
+        self.weights.as_ref().map(|weights| weights.len())
+    }
+
+    fn to_schema(&self) -> Option<RuleSchema> {
+        Some(RuleSchema::Totalistic(TotalisticRuleSchema {
+            condition: SumConditionSchema::from(&self.condition),
+            weights: self.weights.clone(),
+            center: self.center_weight,
+            current: self.current,
+            next: self.next,
+        }))
+    }
+}
+
+impl From<&TotalisticRuleSchema> for TotalisticRule {
+    fn from(schema: &TotalisticRuleSchema) -> Self {
+        let mut rule = TotalisticRule::new(SumCondition::from(&schema.condition), schema.next);
+        if let Some(weights) = &schema.weights {
+            rule.set_weights(weights.clone());
+        }
+        rule.set_center_weight(schema.center);
+        if let Some(current) = schema.current {
+            rule.set_current(current);
+        }
+        rule
+    }
+}
+
+/// rule that fires its wrapped rule with a probability
+#[derive(Clone)]
+pub struct StochasticRule {
+    rule: Box<dyn Rule>,
+    probability: f64,
+    /// weight of each state, when the next state is drawn
+    distribution: Option<Vec<f64>>,
+}
+
+impl StochasticRule {
+    pub fn new(rule: Box<dyn Rule>, probability: f64) -> Self {
+        Self {
+            rule,
+            probability,
+            distribution: None,
+        }
+    }
+
+    /// draw the next value from per-state weights
+    pub fn set_distribution(&mut self, weights: Vec<f64>) -> &mut Self {
+        self.distribution = Some(weights);
+        self
+    }
+
+    pub fn probability(&self) -> f64 {
+        self.probability
+    }
+
+    /// check the probability and the distribution
+    pub fn check(&self) -> Result<(), Error> {
+        if !(0.0..=1.0).contains(&self.probability) {
+            return Err(Error::Rules(format!(
+                "probability must be between 0 and 1, got {}",
+                self.probability
+            )));
+        }
+        if let Some(weights) = &self.distribution {
+            if weights
+                .iter()
+                .any(|weight| !weight.is_finite() || *weight < 0.0)
+                || weights.iter().sum::<f64>() <= 0.0
+            {
+                return Err(Error::Rules(
+                    "a distribution needs non-negative weights with a positive sum".to_string(),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// state where the cumulative weight passes fraction
+    fn draw(weights: &[f64], fraction: f64) -> u32 {
+        let mut target = fraction * weights.iter().sum::<f64>();
+        let mut last = 0;
+        for (state, weight) in weights.iter().enumerate() {
+            if *weight > 0.0 {
+                if target < *weight {
+                    return state as u32;
+                }
+                target -= weight;
+                last = state;
+            }
+        }
+        last as u32
+    }
+}
+
+impl Rule for StochasticRule {
+    fn apply(&self, neighborhood: &Neighborhood) -> Option<u32> {
+        let next = self.rule.apply(neighborhood)?;
+        match &self.distribution {
+            Some(weights) => Some((0..weights.len()).fold(0, |best, state| {
+                if weights[state] > weights[best] {
+                    state
+                } else {
+                    best
+                }
+            }) as u32),
+            None => Some(next),
+        }
+    }
+
+    fn apply_random(&self, neighborhood: &Neighborhood, random: u64) -> Option<u32> {
+        let next = self.rule.apply(neighborhood)?;
+        let mut draws = SplitMix64::new(random);
+        if draws.next_f64() >= self.probability {
+            return None;
+        }
+        match &self.distribution {
+            Some(weights) => Some(StochasticRule::draw(weights, draws.next_f64())),
+            None => Some(next),
+        }
+    }
+
+    fn max_next(&self) -> u32 {
+        match &self.distribution {
+            Some(weights) => weights
+                .iter()
+                .rposition(|weight| *weight > 0.0)
+                .unwrap_or(0) as u32,
+            None => self.rule.max_next(),
+        }
+    }
+
+    fn neighborhood_size(&self) -> Option<usize> {
+        self.rule.neighborhood_size()
+    }
+
+    fn is_deterministic(&self) -> bool {
+        false
+    }
+
+    fn to_schema(&self) -> Option<RuleSchema> {
+        Some(RuleSchema::Stochastic {
+            rule: Box::new(self.rule.to_schema()?),
+            probability: self.probability,
+            distribution: self.distribution.clone(),
+        })
+    }
 }
 
 fn apply_sum_rule_on_predicate(
@@ -186,7 +1243,10 @@ fn apply_sum_rule_on_predicate(
     pred: &dyn Fn(u32, u32) -> bool,
 ) -> Option<u32> {
     if pred(
-        neighborhood.neighbors().iter().sum(),
+        neighborhood
+            .neighbors()
+            .iter()
+            .fold(0u32, |sum, value| sum.saturating_add(*value)),
         sum_rule.neighborhood(),
     ) && sum_rule.current() == neighborhood.cell()
     {
@@ -198,6 +1258,8 @@ fn apply_sum_rule_on_predicate(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::automaton::test_configs::config_rules;
+    use crate::utils::split_mix::derive_seed;
 
     #[test]
     fn should_change_on_equal() {
@@ -239,7 +1301,7 @@ mod tests {
             rule: SumRule::new(4, 1, 0),
         };
         let res = ser.apply(&neighborhood);
-        assert!(!res.is_some());
+        assert!(res.is_none());
     }
 
     #[test]
@@ -249,7 +1311,7 @@ mod tests {
             rule: SumRule::new(4, 1, 0),
         };
         let res = slr.apply(&neighborhood);
-        assert!(!res.is_some());
+        assert!(res.is_none());
     }
     #[test]
     fn should_not_change_on_smaller() {
@@ -258,6 +1320,487 @@ mod tests {
             rule: SumRule::new(5, 1, 0),
         };
         let res = ssr.apply(&neighborhood);
-        assert!(!res.is_some());
+        assert!(res.is_none());
+    }
+
+    #[test]
+    fn sums_should_saturate() {
+        let neighborhood = Neighborhood::new(vec![u32::MAX, u32::MAX], 1);
+        let slr = SumLargerRule {
+            rule: SumRule::new(4, 1, 0),
+        };
+        assert_eq!(slr.apply(&neighborhood), Some(0));
+        assert!(Rules::from_totalistic_code(0, u32::MAX, u32::MAX / 2).is_err());
+    }
+
+    #[test]
+    fn elementary_should_agree_with_explicit() {
+        for number in 0..=255u8 {
+            let elementary = ElementaryRule::new(number);
+            let explicit = Rules::new(
+                elementary
+                    .to_explicit_rules()
+                    .into_iter()
+                    .map(|rule| Box::new(rule) as Box<dyn Rule>)
+                    .collect(),
+            );
+            for pattern in 0..8u32 {
+                let neighborhood =
+                    Neighborhood::new(vec![(pattern >> 2) & 1, pattern & 1], (pattern >> 1) & 1);
+                assert_eq!(
+                    elementary.apply(&neighborhood),
+                    explicit.apply(&neighborhood)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn elementary_110_should_match_config() {
+        let rules = config_rules("rule110/rules.json");
+        let elementary = ElementaryRule::new(110);
+        for pattern in 0..8u32 {
+            let neighborhood =
+                Neighborhood::new(vec![(pattern >> 2) & 1, pattern & 1], (pattern >> 1) & 1);
+            assert_eq!(elementary.apply(&neighborhood), rules.apply(&neighborhood));
+        }
+    }
+
+    #[test]
+    fn elementary_should_not_apply_to_wider_neighborhoods() {
+        let neighborhood = Neighborhood::new(vec![1, 1, 1, 0, 1, 0, 0, 0], 1);
+        assert!(ElementaryRule::new(30).apply(&neighborhood).is_none());
+    }
+
+    fn life_neighborhood(sum: usize, cell: u32) -> Neighborhood {
+        let mut neighbors = vec![0; 8];
+        for n in neighbors.iter_mut().take(sum) {
+            *n = 1;
+        }
+        Neighborhood::new(neighbors, cell)
+    }
+
+    #[test]
+    fn rulestring_should_expand_to_life() {
+        let rules = Rules::from_rulestring("B3/S23").unwrap();
+        for sum in 0..=8 {
+            let born = if sum == 3 { Some(1) } else { None };
+            let survives = if sum == 2 || sum == 3 { 1 } else { 0 };
+            assert_eq!(rules.apply(&life_neighborhood(sum, 0)), born);
+            assert_eq!(rules.apply(&life_neighborhood(sum, 1)), Some(survives));
+        }
+    }
+
+    #[test]
+    fn rulestring_should_match_sum_config() {
+        let config = config_rules("game_of_life/rules_sum.json");
+        let rules = Rules::from_rulestring("b3/s23").unwrap();
+        for sum in 0..=8 {
+            for cell in 0..=1 {
+                let neighborhood = life_neighborhood(sum, cell);
+                assert_eq!(
+                    rules.apply(&neighborhood).unwrap_or(cell),
+                    config.apply(&neighborhood).unwrap_or(cell)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn rulestring_should_accept_highlife_and_seeds() {
+        let highlife = Rules::from_rulestring("B36/S23").unwrap();
+        assert_eq!(highlife.apply(&life_neighborhood(6, 0)), Some(1));
+        let seeds = Rules::from_rulestring("B2/S").unwrap();
+        assert_eq!(seeds.apply(&life_neighborhood(2, 0)), Some(1));
+        for sum in 0..=8 {
+            assert_eq!(seeds.apply(&life_neighborhood(sum, 1)), Some(0));
+        }
+    }
+
+    #[test]
+    fn rulestring_should_accept_legacy_order() {
+        let rules = Rules::from_rulestring("23/36").unwrap();
+        assert_eq!(rules.apply(&life_neighborhood(6, 0)), Some(1));
+        assert_eq!(rules.apply(&life_neighborhood(2, 1)), Some(1));
+        assert_eq!(rules.apply(&life_neighborhood(6, 1)), Some(0));
+    }
+
+    #[test]
+    fn rulestring_should_reject_malformed() {
+        assert!(Rules::from_rulestring("B3S23").is_err());
+        assert!(Rules::from_rulestring("B39/S23").is_err());
+        assert!(Rules::from_rulestring("B3/B23").is_err());
+        assert!(Rules::from_rulestring("B3/S2x").is_err());
+    }
+
+    #[test]
+    fn unknown_sum_rule_type_should_be_an_error() {
+        let schema: RulesSchema = serde_json::from_str(
+            r#"{ "sum_rules": [{ "rule_type": 3, "neighborhood": 2, "current": 0, "next": 1 }] }"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            Rules::try_from(&schema),
+            Err(Error::InvalidRuleType(3))
+        ));
+    }
+
+    /// fraction of `draws` random values for which the rule gives `value`
+    fn frequency(rule: &dyn Rule, neighborhood: &Neighborhood, value: u32, draws: u64) -> f64 {
+        let hits = (0..draws)
+            .filter(|draw| rule.apply_random(neighborhood, derive_seed(1, *draw)) == Some(value))
+            .count();
+        hits as f64 / draws as f64
+    }
+
+    #[test]
+    fn rules_should_be_listed_in_order() {
+        let schema: RulesSchema = serde_json::from_str(
+            r#"{
+                "rules": [
+                    {
+                        "type": "stochastic",
+                        "rule": { "type": "explicit", "neighborhood": [0, 0], "current": 1, "next": 0 },
+                        "probability": 0.5
+                    },
+                    { "type": "sum", "rule_type": 1, "neighborhood": 1, "current": 0, "next": 2 },
+                    { "type": "elementary", "number": 90 }
+                ],
+                "states": 3
+            }"#,
+        )
+        .unwrap();
+        let rules = Rules::try_from(&schema).unwrap();
+        assert_eq!(rules.apply(&Neighborhood::new(vec![0, 0], 1)), Some(0));
+        assert_eq!(rules.apply(&Neighborhood::new(vec![1, 1], 0)), Some(2));
+        assert_eq!(rules.apply(&Neighborhood::new(vec![1, 0], 1)), Some(1));
+        // written out and read back, the rules give every neighborhood the
+        // same value
+        let written = Rules::try_from(&rules.to_schema().unwrap()).unwrap();
+        for index in 0..27 {
+            let neighborhood = Neighborhood::from_index(index, 3, 2);
+            assert_eq!(
+                written.apply_random(&neighborhood, index as u64),
+                rules.apply_random(&neighborhood, index as u64)
+            );
+        }
+        assert_eq!(written.states(), 3);
+
+        #[derive(Clone)]
+        struct Unwritable;
+        impl Rule for Unwritable {
+            fn apply(&self, _neighborhood: &Neighborhood) -> Option<u32> {
+                None
+            }
+            fn max_next(&self) -> u32 {
+                0
+            }
+        }
+        assert!(Rules::new(vec![Box::new(Unwritable)]).to_schema().is_err());
+    }
+
+    #[test]
+    fn stochastic_rule_should_fire_with_probability() {
+        let always = TotalisticRule::new(SumCondition::Range(0, u32::MAX), 1);
+        let rule = StochasticRule::new(Box::new(always), 0.3);
+        let neighborhood = Neighborhood::new(vec![0, 1], 0);
+        assert!((frequency(&rule, &neighborhood, 1, 10000) - 0.3).abs() < 0.02);
+        assert_eq!(rule.apply(&neighborhood), Some(1));
+        assert!(!rule.is_deterministic());
+
+        let mut rule = rule.clone();
+        rule.set_distribution(vec![0.0, 1.0, 3.0]);
+        assert_eq!(rule.max_next(), 2);
+        assert_eq!(rule.apply(&neighborhood), Some(2));
+        assert_eq!(frequency(&rule, &neighborhood, 0, 1000), 0.0);
+        assert!((frequency(&rule, &neighborhood, 1, 10000) - 0.3 * 0.25).abs() < 0.02);
+    }
+
+    #[test]
+    fn stochastic_rules_should_be_read_from_schema() {
+        let schema: RulesSchema = serde_json::from_str(
+            r#"{
+                "rulestring": "B3/S23",
+                "rules": [{
+                    "type": "stochastic",
+                    "rule": { "type": "totalistic", "condition": { "range": [0, 8] }, "next": 0 },
+                    "probability": 0.01,
+                    "distribution": [1, 1]
+                }]
+            }"#,
+        )
+        .unwrap();
+        let rules = Rules::try_from(&schema).unwrap();
+        assert!(!rules.is_deterministic());
+        assert!(rules.as_life_like().is_none());
+        // the noise rule is checked first and taken to fire when analyzed,
+        // but does not shadow the rules after it
+        let analysis = rules.analyze(2).unwrap();
+        let life = Rules::from_rulestring("B3/S23")
+            .unwrap()
+            .analyze(2)
+            .unwrap();
+        assert!(analysis.conflicts.is_empty());
+        assert_eq!(
+            analysis.shadowed,
+            life.shadowed
+                .iter()
+                .map(|rule| rule + 1)
+                .collect::<Vec<_>>()
+        );
+
+        for rules in &[
+            r#"{ "rules": [{ "type": "stochastic", "rule": { "type": "elementary", "number": 30 }, "probability": 1.5 }] }"#,
+            r#"{ "rules": [{ "type": "stochastic", "rule": { "type": "elementary", "number": 30 }, "probability": 0.5, "distribution": [1, -1] }] }"#,
+        ] {
+            let schema: RulesSchema = serde_json::from_str(rules).unwrap();
+            assert!(Rules::try_from(&schema).is_err(), "{}", rules);
+        }
+    }
+
+    #[test]
+    fn analyze_should_find_conflicts_shadows_and_gaps() {
+        let rules = Rules::new(vec![
+            Box::new(ElementaryRule::new(30)),
+            Box::new(ExplicitRule::new(vec![1, 1], 1, 1)),
+            Box::new(ExplicitRule::new(vec![0, 0], 0, 0)),
+        ]);
+        let analysis = rules.analyze(1).unwrap();
+        assert_eq!(analysis.neighborhoods, 8);
+        // rule 30 gives 0 for 111
+        assert_eq!(
+            analysis.conflicts,
+            vec![Conflict {
+                first: 0,
+                second: 1,
+                neighbors: vec![1, 1],
+                current: 1,
+                first_next: 0,
+                second_next: 1,
+            }]
+        );
+        assert_eq!(analysis.shadowed, vec![1, 2]);
+        assert!(analysis.never_match.is_empty());
+        assert!(analysis.uncovered.is_empty());
+    }
+
+    #[test]
+    fn analyze_should_tell_rules_that_never_match_from_shadowed() {
+        let rules = Rules::new(vec![
+            Box::new(ExplicitRule::new(vec![0, 0], 0, 0)),
+            Box::new(ExplicitRule::new(vec![0, 0], 0, 1)),
+            Box::new(SumEqualRule::new(SumRule::new(3, 1, 0))),
+            Box::new(ExplicitRule::new(vec![0, 0, 0], 0, 1)),
+        ]);
+        let analysis = rules.analyze(1).unwrap();
+        assert_eq!(analysis.shadowed, vec![1]);
+        assert_eq!(analysis.never_match, vec![2, 3]);
+        assert!(analysis
+            .to_string()
+            .contains("rules that match no neighborhood: 2"));
+    }
+
+    #[test]
+    fn analyze_should_list_uncovered_neighborhoods() {
+        let mut rules = config_rules("rule110/rules.json");
+        let analysis = rules.analyze(1).unwrap();
+        assert!(analysis.conflicts.is_empty());
+        assert!(analysis.shadowed.is_empty());
+        assert!(analysis.uncovered.is_empty());
+        rules.set_states(3);
+        assert_eq!(rules.analyze(1).unwrap().uncovered.len(), 27 - 8);
+        assert!(rules.analyze(3).is_err());
+    }
+
+    #[test]
+    fn next_state_should_follow_unmatched_policy() {
+        let mut rules = Rules::new(vec![Box::new(ExplicitRule::new(vec![0, 0], 1, 1))]);
+        let matched = Neighborhood::new(vec![0, 0], 1);
+        let unmatched = Neighborhood::new(vec![1, 0], 1);
+        assert_eq!(rules.next_state(&unmatched), Some(1));
+        rules.set_unmatched(Unmatched::Default(0));
+        assert_eq!(rules.next_state(&unmatched), Some(0));
+        rules.set_unmatched(Unmatched::Error);
+        assert_eq!(rules.next_state(&unmatched), None);
+        assert_eq!(rules.next_state(&matched), Some(1));
+    }
+
+    #[test]
+    fn unmatched_policy_should_be_read_from_schema() {
+        let schema: RulesSchema =
+            serde_json::from_str(r#"{ "wolfram": 90, "unmatched": { "default": 1 } }"#).unwrap();
+        let rules = Rules::try_from(&schema).unwrap();
+        assert_eq!(rules.unmatched(), Unmatched::Default(1));
+        let schema: RulesSchema =
+            serde_json::from_str(r#"{ "wolfram": 90, "unmatched": { "default": 2 } }"#).unwrap();
+        assert!(Rules::try_from(&schema).is_err());
+        let schema: RulesSchema =
+            serde_json::from_str(r#"{ "wolfram": 90, "unmatched": "error" }"#).unwrap();
+        assert_eq!(
+            Rules::try_from(&schema).unwrap().unmatched(),
+            Unmatched::Error
+        );
+    }
+
+    #[test]
+    fn should_recognize_elementary_rules() {
+        for number in &[0u8, 30, 90, 110, 255] {
+            let explicit = Rules::new(
+                ElementaryRule::new(*number)
+                    .to_explicit_rules()
+                    .into_iter()
+                    .map(|rule| Box::new(rule) as Box<dyn Rule>)
+                    .collect(),
+            );
+            assert_eq!(explicit.as_elementary(), Some(*number));
+        }
+        let mut rules = Rules::new(vec![Box::new(ElementaryRule::new(30))]);
+        rules.set_states(3);
+        assert_eq!(rules.as_elementary(), None);
+    }
+
+    #[test]
+    fn should_recognize_life_like_rules() {
+        let highlife = Rules::from_rulestring("B36/S23").unwrap();
+        assert_eq!(highlife.as_life_like(), Some((0b100_1000, 0b1100)));
+        // depends on where the live neighbor is, not only how many there are
+        let directed = Rules::new(vec![Box::new(ExplicitRule::new(
+            vec![1, 0, 0, 0, 0, 0, 0, 0],
+            0,
+            1,
+        ))]);
+        assert_eq!(directed.as_life_like(), None);
+    }
+
+    #[test]
+    fn totalistic_should_weigh_neighbors_and_center() {
+        let neighborhood = Neighborhood::new(vec![1, 2, 0, 1], 2);
+        let mut rule = TotalisticRule::new(SumCondition::Set(vec![4, 9]), 1);
+        assert_eq!(rule.apply(&neighborhood), Some(1));
+        rule.set_center_weight(1);
+        assert_eq!(rule.sum(&neighborhood), 6);
+        assert_eq!(rule.apply(&neighborhood), None);
+        rule.set_weights(vec![2, 2, 5, 1]);
+        assert_eq!(rule.apply(&neighborhood), Some(1));
+        rule.set_current(1);
+        assert_eq!(rule.apply(&neighborhood), None);
+        assert_eq!(rule.neighborhood_size(), Some(4));
+    }
+
+    #[test]
+    fn totalistic_sum_should_saturate() {
+        let mut rule = TotalisticRule::new(SumCondition::Set(vec![u32::MAX]), 1);
+        rule.set_weights(vec![u32::MAX, 2]).set_center_weight(3);
+        let neighborhood = Neighborhood::new(vec![2, 1], 1);
+        assert_eq!(rule.sum(&neighborhood), u32::MAX);
+        assert_eq!(rule.apply(&neighborhood), Some(1));
+        let rule = TotalisticRule::new(SumCondition::Set(vec![u32::MAX]), 1);
+        let neighborhood = Neighborhood::new(vec![u32::MAX, 1], 0);
+        assert_eq!(rule.sum(&neighborhood), u32::MAX);
+    }
+
+    #[test]
+    fn totalistic_conditions_should_match_sums() {
+        let majority = SumCondition::Range(5, 9);
+        assert!(!majority.matches(4));
+        assert!(majority.matches(5));
+        assert!(majority.matches(9));
+        let parity = SumCondition::Modulo {
+            modulus: 2,
+            remainder: 1,
+        };
+        assert!(parity.matches(7));
+        assert!(!parity.matches(4));
+        let never = SumCondition::Modulo {
+            modulus: 0,
+            remainder: 0,
+        };
+        assert!(!never.matches(0));
+    }
+
+    #[test]
+    fn totalistic_code_should_agree_with_elementary() {
+        // code 10 is 1010 in binary: a cell is 1 when it and its two
+        // neighbors sum to 1 or 3, as in rule 150
+        let rules = Rules::from_totalistic_code(10, 2, 1).unwrap();
+        let elementary = ElementaryRule::new(150);
+        for pattern in 0..8u32 {
+            let neighborhood =
+                Neighborhood::new(vec![(pattern >> 2) & 1, pattern & 1], (pattern >> 1) & 1);
+            assert_eq!(rules.apply(&neighborhood), elementary.apply(&neighborhood));
+        }
+        assert!(Rules::from_totalistic_code(16, 2, 1).is_err());
+        let rules = Rules::from_totalistic_code(777, 3, 1).unwrap();
+        assert_eq!(rules.states(), 3);
+        // 777 is 1001210 in base 3, so a sum of 2 gives 2
+        let neighborhood = Neighborhood::new(vec![1, 0], 1);
+        assert_eq!(rules.apply(&neighborhood), Some(2));
+    }
+
+    #[test]
+    fn totalistic_code_should_be_read_from_schema() {
+        let schema: RulesSchema = serde_json::from_str(
+            r#"{ "totalistic_code": 777, "states": 3, "neighborhood": { "shape": "moore", "radius": 2 } }"#,
+        )
+        .unwrap();
+        let rules = Rules::try_from(&schema).unwrap();
+        assert_eq!(rules.states(), 3);
+        assert_eq!(rules.shape(), &NeighborhoodShape::Moore(2));
+        let schema: RulesSchema = serde_json::from_str(
+            r#"{ "totalistic_code": 777, "neighborhood": { "shape": "hexagonal" } }"#,
+        )
+        .unwrap();
+        assert!(Rules::try_from(&schema).is_err());
+    }
+
+    #[test]
+    fn generations_should_cycle_through_refractory_states() {
+        let brain = Rules::from_rulestring("/2/3").unwrap();
+        assert_eq!(brain.states(), 3);
+        let two_alive = Neighborhood::new(vec![1, 1, 2, 0, 0, 0, 0, 0], 0);
+        assert_eq!(brain.apply(&two_alive), Some(1));
+        let three_alive = Neighborhood::new(vec![1, 1, 1, 0, 0, 0, 0, 0], 0);
+        assert_eq!(brain.apply(&three_alive), Some(0));
+        assert_eq!(brain.apply(&Neighborhood::new(vec![0; 8], 1)), Some(2));
+        assert_eq!(brain.apply(&Neighborhood::new(vec![0; 8], 2)), Some(0));
+    }
+
+    #[test]
+    fn generations_should_accept_letter_form() {
+        let rules = Rules::from_rulestring("B2/S34/C5").unwrap();
+        assert_eq!(rules.states(), 5);
+        let three_alive = Neighborhood::new(vec![1, 1, 1, 0, 0, 0, 0, 0], 1);
+        assert_eq!(rules.apply(&three_alive), Some(1));
+        assert_eq!(rules.apply(&Neighborhood::new(vec![0; 8], 1)), Some(2));
+        assert_eq!(rules.apply(&Neighborhood::new(vec![0; 8], 4)), Some(0));
+        assert!(Rules::from_rulestring("B2/S/C1").is_err());
+    }
+
+    #[test]
+    fn rulestring_should_not_lower_generations_states() {
+        let schema: RulesSchema = serde_json::from_str(
+            r#"{
+                "rules": [{ "type": "generations", "birth": [2], "survival": [], "states": 6 }],
+                "rulestring": "B2/S34/C3"
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(Rules::try_from(&schema).unwrap().states(), 6);
+    }
+
+    #[test]
+    fn validate_should_reject_next_out_of_range() {
+        let mut rules = Rules::new(vec![Box::new(ExplicitRule::new(vec![0, 0], 0, 2))]);
+        assert!(rules.validate().is_err());
+        rules.set_states(3);
+        assert!(rules.validate().is_ok());
+    }
+
+    #[test]
+    fn check_shape_should_compare_explicit_rule_length() {
+        let mut rules = Rules::new(vec![Box::new(ExplicitRule::new(vec![0; 4], 0, 1))]);
+        assert!(rules.check_shape(2).is_err());
+        rules.set_shape(NeighborhoodShape::VonNeumann(1));
+        assert!(rules.check_shape(2).is_ok());
     }
 }
@@ -0,0 +1,194 @@
+use crate::automaton::parsers::schemas::ScheduleSchema;
+use crate::error::Error;
+use crate::utils::split_mix::SplitMix64;
+use crate::utils::{coordinate_of, index_of};
+use std::borrow::Cow;
+
+/// order the cells of a generation are updated in
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum Schedule {
+    /// every cell at once, from the previous generation
+    #[default]
+    Synchronous,
+    /// every cell once, in a random order each generation
+    RandomSequential,
+    /// every cell once, one after another in the given order
+    Sweep(Vec<usize>),
+    /// each cell with the given probability, all at once
+    RandomIndependent(f64),
+    /// the given blocks one after another, the cells of a block at once
+    BlockSequential(Vec<Vec<usize>>),
+}
+
+impl Schedule {
+    /// a sweep through every cell in row-major order
+    pub fn sweep(dims: &[usize]) -> Self {
+        Schedule::Sweep((0..dims.iter().product()).collect())
+    }
+
+    /// whether cells are updated one after another
+    pub fn is_sequential(&self) -> bool {
+        matches!(
+            self,
+            Schedule::RandomSequential | Schedule::Sweep(_) | Schedule::BlockSequential(_)
+        )
+    }
+
+    /// check the schedule fits the number of cells
+    pub fn check(&self, cells: usize) -> Result<(), Error> {
+        match self {
+            Schedule::Sweep(order) => check_partition(std::iter::once(order), cells),
+            Schedule::BlockSequential(blocks) => check_partition(blocks.iter(), cells),
+            Schedule::RandomIndependent(probability) if !(0.0..=1.0).contains(probability) => {
+                let cause = format!(
+                    "update probability must be between 0 and 1, got {}",
+                    probability
+                );
+                Err(Error::Schedule(cause))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// cells updated one after another this generation
+    pub fn sequence(&self, cells: usize, seed: u64) -> Cow<'_, [usize]> {
+        match self {
+            Schedule::RandomSequential => {
+                let mut order: Vec<usize> = (0..cells).collect();
+                let mut rng = SplitMix64::new(seed);
+                for last in (1..cells).rev() {
+                    order.swap(last, (rng.next_u64() % (last as u64 + 1)) as usize);
+                }
+                Cow::Owned(order)
+            }
+            Schedule::Sweep(order) => Cow::Borrowed(&order[..]),
+            _ => Cow::Owned(Vec::new()),
+        }
+    }
+
+    /// config of the schedule
+    pub fn to_schema(&self, dims: &[usize]) -> ScheduleSchema {
+        let points = |cells: &[usize]| -> Vec<Vec<usize>> {
+            cells
+                .iter()
+                .map(|cell| coordinate_of(*cell, dims))
+                .collect()
+        };
+        match self {
+            Schedule::Synchronous => ScheduleSchema::Synchronous,
+            Schedule::RandomSequential => ScheduleSchema::RandomSequential,
+            Schedule::Sweep(order) => ScheduleSchema::Sweep {
+                order: Some(points(order)),
+            },
+            Schedule::RandomIndependent(probability) => ScheduleSchema::RandomIndependent {
+                probability: *probability,
+            },
+            Schedule::BlockSequential(blocks) => ScheduleSchema::BlockSequential {
+                blocks: blocks.iter().map(|block| points(block)).collect(),
+            },
+        }
+    }
+
+    /// build a schedule from its config
+    pub fn from_schema(schema: &ScheduleSchema, dims: &[usize]) -> Result<Self, Error> {
+        let schedule = match schema {
+            ScheduleSchema::Synchronous => Schedule::Synchronous,
+            ScheduleSchema::RandomSequential => Schedule::RandomSequential,
+            ScheduleSchema::Sweep { order: None } => Schedule::sweep(dims),
+            ScheduleSchema::Sweep { order: Some(order) } => Schedule::Sweep(indices(order, dims)?),
+            ScheduleSchema::RandomIndependent { probability } => {
+                Schedule::RandomIndependent(*probability)
+            }
+            ScheduleSchema::BlockSequential { blocks } => Schedule::BlockSequential(
+                blocks
+                    .iter()
+                    .map(|block| indices(block, dims))
+                    .collect::<Result<_, _>>()?,
+            ),
+        };
+        schedule.check(dims.iter().product())?;
+        Ok(schedule)
+    }
+}
+
+/// linear indices of coordinates inside the given dimensions
+fn indices(points: &[Vec<usize>], dims: &[usize]) -> Result<Vec<usize>, Error> {
+    points
+        .iter()
+        .map(|point| {
+            index_of(point, dims).ok_or_else(|| Error::OutOfBounds {
+                point: point.clone(),
+                dims: dims.to_vec(),
+            })
+        })
+        .collect()
+}
+
+/// check that every cell is in exactly one of the groups
+fn check_partition<'a>(
+    groups: impl Iterator<Item = &'a Vec<usize>>,
+    cells: usize,
+) -> Result<(), Error> {
+    let mut seen = vec![false; cells];
+    for cell in groups.flatten() {
+        match seen.get_mut(*cell) {
+            Some(seen) if !*seen => *seen = true,
+            Some(_) => {
+                let cause = format!("cell {} is updated more than once", cell);
+                return Err(Error::Schedule(cause));
+            }
+            None => {
+                let cause = format!("cell {} is outside of the {} cells", cell, cells);
+                return Err(Error::Schedule(cause));
+            }
+        }
+    }
+    if let Some(cell) = seen.iter().position(|seen| !seen) {
+        let cause = format!("cell {} is never updated", cell);
+        return Err(Error::Schedule(cause));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn schedules_should_be_read_from_schema() {
+        let schema: ScheduleSchema = serde_json::from_str(
+            r#"{ "type": "block_sequential", "blocks": [[[0, 1], [1, 0]], [[0, 0], [1, 1]]] }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            Schedule::from_schema(&schema, &[2, 2]).unwrap(),
+            Schedule::BlockSequential(vec![vec![1, 2], vec![0, 3]])
+        );
+        let schema: ScheduleSchema = serde_json::from_str(r#"{ "type": "sweep" }"#).unwrap();
+        assert_eq!(
+            Schedule::from_schema(&schema, &[2, 2]).unwrap(),
+            Schedule::Sweep(vec![0, 1, 2, 3])
+        );
+    }
+
+    #[test]
+    fn schedules_should_update_every_cell_once() {
+        assert!(Schedule::Sweep(vec![0, 2, 1]).check(3).is_ok());
+        assert!(Schedule::Sweep(vec![0, 2, 2]).check(3).is_err());
+        assert!(Schedule::Sweep(vec![0, 1]).check(3).is_err());
+        assert!(Schedule::BlockSequential(vec![vec![0], vec![1, 3]])
+            .check(3)
+            .is_err());
+        assert!(Schedule::RandomIndependent(1.5).check(3).is_err());
+    }
+
+    #[test]
+    fn random_sequence_should_permute_cells() {
+        let first = Schedule::RandomSequential.sequence(100, 1).into_owned();
+        let mut sorted = first.clone();
+        sorted.sort_unstable();
+        assert_eq!(sorted, (0..100).collect::<Vec<_>>());
+        assert_eq!(Schedule::RandomSequential.sequence(100, 1), first);
+        assert_ne!(Schedule::RandomSequential.sequence(100, 2), first);
+    }
+}
@@ -0,0 +1,23 @@
+use crate::automaton::parsers::parse_file_to_schema;
+use crate::automaton::parsers::schemas::RulesSchema;
+use crate::automaton::rules::Rules;
+use serde::de::DeserializeOwned;
+use std::convert::TryFrom;
+use std::path::PathBuf;
+
+/// path of a file under config/
+pub fn config_path(path: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("config")
+        .join(path)
+}
+
+/// read a file under config/
+pub fn read_config<T: DeserializeOwned>(path: &str) -> T {
+    parse_file_to_schema(&config_path(path)).unwrap()
+}
+
+/// read a rules file under config/
+pub fn config_rules(path: &str) -> Rules {
+    Rules::try_from(&read_config::<RulesSchema>(path)).unwrap()
+}
@@ -1,34 +1,225 @@
 use crate::automaton::automaton_builder::AutomatonBuilder;
-use crate::automaton::parsers::parse_file_to_schema;
-use crate::automaton::parsers::schemas::{CoordinatesSchema, DimensionsSchema, RulesSchema};
+use crate::automaton::parsers::rle::RlePattern;
+use crate::automaton::parsers::schemas::{
+    DimensionsSchema, IncludeSchema, RuleMapSchema, RulesSchema, ScenarioSchema,
+};
+use crate::automaton::parsers::{
+    parse_file_to_schema, parse_scenario, parse_starting_cells, StartingCells,
+};
 use crate::automaton::rules::Rules;
 use crate::automaton::Automaton;
-use exitfailure::ExitFailure;
+use crate::error::Error;
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io::{BufWriter, Write};
 use std::path::PathBuf;
+use structopt::clap::{Error as ClapError, ErrorKind};
 use structopt::StructOpt;
 
-pub fn cli() -> Result<Automaton, ExitFailure> {
+/// build the automaton for the menu, or run a subcommand
+pub fn cli() -> Result<Option<Automaton>, Error> {
     let opt = Opt::from_args();
 
-    let rules_schema = parse_file_to_schema::<RulesSchema>(&opt.path_to_rules)?;
+    if let Some(Command::CheckRules {
+        path_to_rules,
+        path_to_dimensions,
+    }) = &opt.command
+    {
+        check_rules(path_to_rules, path_to_dimensions)?;
+        return Ok(None);
+    }
 
-    let rules = Rules::from(&rules_schema);
+    let mut automaton = build(&opt)?;
 
-    let dimensions_schema = parse_file_to_schema::<DimensionsSchema>(&opt.path_to_dimensions)?;
+    if let Some(Command::Run {
+        generations,
+        every,
+        path_to_output,
+    }) = &opt.command
+    {
+        if *every == Some(0) {
+            ClapError::with_description("--every must be at least 1", ErrorKind::InvalidValue)
+                .exit();
+        }
+        run(&mut automaton, *generations, *every, path_to_output)?;
+        return Ok(None);
+    }
 
-    let mut ab = AutomatonBuilder::new(dimensions_schema.dimensions);
+    Ok(Some(automaton))
+}
 
-    let coordinates_schema = parse_file_to_schema::<CoordinatesSchema>(&opt.path_to_coordinates)?;
+/// build the automaton from the command line
+fn build(opt: &Opt) -> Result<Automaton, Error> {
+    let (scenario, pattern) = match &opt.path_to_scenario {
+        Some(path) => (parse_scenario(path)?, None),
+        None => scenario_from_files(opt)?,
+    };
+    let mut ab = AutomatonBuilder::from_scenario(&scenario)?;
+    if let Some(pattern) = &pattern {
+        let offset = if opt.offset.is_empty() {
+            vec![0, 0]
+        } else {
+            opt.offset.clone()
+        };
+        ab.set_pattern(pattern, &offset)?;
+    }
+    if let Some(seed) = opt.seed {
+        ab.set_seed(seed);
+    }
+    ab.build()
+}
 
-    for coordinate in coordinates_schema.coordinates {
-        ab.set_point(&coordinate[..])?;
+/// scenario from separate config files, and an RLE pattern to place
+fn scenario_from_files(opt: &Opt) -> Result<(ScenarioSchema, Option<RlePattern>), Error> {
+    let (coordinates, pattern) =
+        match parse_starting_cells(required(&opt.path_to_coordinates, "coordinates"))? {
+            StartingCells::Pattern(pattern) => (Vec::new(), Some(pattern)),
+            StartingCells::Coordinates(schema) => (shift(schema.coordinates, &opt.offset)?, None),
+        };
+    let header_rules = pattern
+        .as_ref()
+        .and_then(RlePattern::rulestring)
+        .map(|rulestring| RulesSchema {
+            rulestring: Some(rulestring.to_string()),
+            ..RulesSchema::default()
+        });
+    // a subcommand lifts the requirement clap checks on its own
+    if opt.path_to_rules.is_none()
+        && opt.wolfram.is_none()
+        && opt.path_to_rule_map.is_none()
+        && header_rules.is_none()
+    {
+        ClapError::with_description(
+            "one of --scenario, --rules, --wolfram or --rule-map is required",
+            ErrorKind::MissingRequiredArgument,
+        )
+        .exit();
     }
-    let automaton = ab.set_rules(rules).build()?;
+    let rules = match (opt.wolfram, &opt.path_to_rules) {
+        (Some(number), _) => Some(RulesSchema {
+            wolfram: Some(number),
+            ..RulesSchema::default()
+        }),
+        (None, Some(path)) => Some(parse_file_to_schema::<RulesSchema>(path)?),
+        (None, None) => None,
+    };
+    let rule_map = match &opt.path_to_rule_map {
+        Some(path) => Some(parse_file_to_schema::<RuleMapSchema>(path)?),
+        None => None,
+    };
+    // the rule of the pattern applies unless other rules are given
+    let rules = match (rules, &rule_map, &pattern) {
+        (None, None, _) => header_rules,
+        (Some(rules), _, Some(pattern)) => {
+            pattern.check_rules(&Rules::try_from(&rules)?)?;
+            Some(rules)
+        }
+        (rules, _, _) => rules,
+    };
+    if let (Some(map), Some(pattern)) = (&rule_map, &pattern) {
+        for rules in &map.rules {
+            pattern.check_rules(&Rules::try_from(rules)?)?;
+        }
+    }
+    let scenario = ScenarioSchema {
+        dimensions: parse_file_to_schema(required(&opt.path_to_dimensions, "dimensions"))?,
+        rules: rules.map(IncludeSchema::Inline),
+        rule_map: rule_map.map(IncludeSchema::Inline),
+        coordinates,
+        seed: None,
+    };
+    Ok((scenario, pattern))
+}
 
-    Ok(automaton)
+/// move every coordinate by an offset, if one is given
+fn shift(coordinates: Vec<Vec<usize>>, offset: &[usize]) -> Result<Vec<Vec<usize>>, Error> {
+    if offset.is_empty() {
+        return Ok(coordinates);
+    }
+    coordinates
+        .into_iter()
+        .map(|point| {
+            if point.len() != offset.len() {
+                return Err(Error::DimensionMismatch {
+                    expected: point.len(),
+                    found: offset.len(),
+                });
+            }
+            Ok(point.iter().zip(offset).map(|(x, o)| x + o).collect())
+        })
+        .collect()
 }
 
-/// This tool allows you to simulate a toroidal cellular automaton with
+/// advance the automaton and write generations to a file
+// `is_multiple_of` needs Rust 1.87
+#[allow(clippy::manual_is_multiple_of)]
+pub fn run(
+    automaton: &mut Automaton,
+    generations: u32,
+    every: Option<u32>,
+    path_to_output: &PathBuf,
+) -> Result<(), Error> {
+    let io_error = |source| Error::Io {
+        path: path_to_output.clone(),
+        source,
+    };
+    if every == Some(0) {
+        return Err(Error::Automaton(
+            "generations can only be written every 1 or more".to_string(),
+        ));
+    }
+    let last = automaton
+        .generation()
+        .checked_add(generations)
+        .ok_or_else(|| {
+            Error::Automaton(format!(
+                "cannot run {} generations past generation {}",
+                generations,
+                automaton.generation()
+            ))
+        })?;
+    let mut out = BufWriter::new(File::create(path_to_output).map_err(io_error)?);
+    loop {
+        let generation = automaton.generation();
+        if generation == last || matches!(every, Some(every) if generation % every == 0) {
+            writeln!(
+                out,
+                "generation {}\n{}",
+                generation,
+                automaton.grid().render_plain()
+            )
+            .map_err(io_error)?;
+        }
+        if generation == last {
+            break;
+        }
+        automaton.advance()?;
+    }
+    out.flush().map_err(io_error)
+}
+
+/// an option required without a subcommand
+fn required<'a>(value: &'a Option<PathBuf>, name: &str) -> &'a PathBuf {
+    value.as_ref().unwrap_or_else(|| {
+        ClapError::with_description(
+            &format!("--{} is required unless a subcommand is given", name),
+            ErrorKind::MissingRequiredArgument,
+        )
+        .exit()
+    })
+}
+
+/// print a report of the rules
+fn check_rules(path_to_rules: &PathBuf, path_to_dimensions: &PathBuf) -> Result<(), Error> {
+    let rules = Rules::try_from(&parse_file_to_schema::<RulesSchema>(path_to_rules)?)?;
+    let dimensions = parse_file_to_schema::<DimensionsSchema>(path_to_dimensions)?.dimensions;
+    rules.validate()?;
+    rules.check_shape(dimensions.len())?;
+    print!("{}", rules.analyze(dimensions.len())?);
+    Ok(())
+}
+
+/// This tool allows you to simulate a cellular automaton with
 /// any number of dimensions.
 /// To read more about a cellular automata go to:
 /// https://mathworld.wolfram.com/CellularAutomaton.html
@@ -36,7 +227,8 @@ pub fn cli() -> Result<Automaton, ExitFailure> {
 #[structopt(
     name = "cellular-automaton",
     version = "2.1.4",
-    author = "Matan Orland (matan.orland@gmail.com)"
+    author = "Matan Orland (matan.orland@gmail.com)",
+    raw(setting = "structopt::clap::AppSettings::SubcommandsNegateReqs")
 )]
 struct Opt {
     /// Path to cellular automata config file
@@ -45,30 +237,147 @@ struct Opt {
         long = "rules",
         short = "r",
         parse(from_os_str),
-        raw(required = "true")
+        raw(
+            required_unless_one = "&[\"wolfram\", \"path_to_rule_map\", \"path_to_scenario\", \"path_to_coordinates\"]"
+        )
     )]
-    path_to_rules: PathBuf,
+    path_to_rules: Option<PathBuf>,
 
-    /// Path to cellular automata config file
-    /// (see config/rule110.json as an example)
+    /// Wolfram elementary rule number (0-255) to use instead of a rules file
     #[structopt(
-        long = "dimensions",
-        short = "d",
+        long = "wolfram",
+        short = "w",
+        raw(conflicts_with = "\"path_to_rules\"")
+    )]
+    wolfram: Option<u8>,
+
+    /// Path to rule map config file, giving each cell its own rules
+    #[structopt(
+        long = "rule-map",
+        short = "m",
         parse(from_os_str),
-        raw(required = "true")
+        raw(conflicts_with_all = "&[\"path_to_rules\", \"wolfram\"]")
     )]
-    path_to_dimensions: PathBuf,
+    path_to_rule_map: Option<PathBuf>,
+
+    /// Path to cellular automata config file
+    /// (see config/rule110.json as an example)
+    #[structopt(long = "dimensions", short = "d", parse(from_os_str))]
+    path_to_dimensions: Option<PathBuf>,
 
     /// Path to cpprdonates config file
     /// coordinates should have the form:
     ///     [x_1,y_1,...]
     ///     [x_2,y_2,...]
-    /// in a JSON file (see config/rule110_coordinates.json as an example)
+    /// in a JSON file (see config/rule110_coordinates.json as an example),
+    /// or an .rle, .cells or .lif pattern file
+    #[structopt(long = "coordinates", short = "c", parse(from_os_str))]
+    path_to_coordinates: Option<PathBuf>,
+
+    /// Offset of the starting cells, e.g. 10,20
+    #[structopt(
+        long = "offset",
+        raw(use_delimiter = "true"),
+        raw(conflicts_with = "\"path_to_scenario\"")
+    )]
+    offset: Vec<usize>,
+
+    /// Path to scenario config file holding dimensions, rules and cells
+    /// (see config/scenarios/glider.json as an example)
     #[structopt(
-        long = "coordinates",
-        short = "c",
+        long = "scenario",
+        short = "S",
         parse(from_os_str),
-        raw(required = "true")
+        raw(
+            conflicts_with_all = "&[\"path_to_rules\", \"wolfram\", \"path_to_rule_map\", \"path_to_dimensions\", \"path_to_coordinates\"]"
+        )
     )]
-    path_to_coordinates: PathBuf,
+    path_to_scenario: Option<PathBuf>,
+
+    /// Seed for stochastic rules (0 if not given)
+    #[structopt(long = "seed", short = "s")]
+    seed: Option<u64>,
+
+    #[structopt(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(StructOpt, Debug)]
+enum Command {
+    /// Advance the automaton without prompting and write generations to a file
+    #[structopt(name = "run")]
+    Run {
+        /// Number of generations to advance
+        #[structopt(long = "generations", short = "g")]
+        generations: u32,
+
+        /// Also write every generation whose number is a multiple of this
+        #[structopt(long = "every", short = "e")]
+        every: Option<u32>,
+
+        /// Path to the file generations are written to
+        #[structopt(long = "output", short = "o", parse(from_os_str))]
+        path_to_output: PathBuf,
+    },
+
+    /// Show the automaton and advance it from a menu (the default)
+    #[structopt(name = "interactive")]
+    Interactive,
+
+    /// Report colliding, unused and missing rules
+    #[structopt(name = "check-rules")]
+    CheckRules {
+        /// Path to cellular automata config file
+        #[structopt(long = "rules", short = "r", parse(from_os_str))]
+        path_to_rules: PathBuf,
+
+        /// Path to dimensions config file, for the number of dimensions
+        #[structopt(long = "dimensions", short = "d", parse(from_os_str))]
+        path_to_dimensions: PathBuf,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn run_should_write_chosen_generations() {
+        let mut automaton = AutomatonBuilder::new(vec![5])
+            .set_point(&[2])
+            .unwrap()
+            .set_elementary_rule(90)
+            .build()
+            .unwrap();
+        let path = std::env::temp_dir().join("cellular-automaton-run-test.txt");
+        run(&mut automaton, 3, Some(2), &path).unwrap();
+        let written = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(
+            written,
+            "generation 0\n0 0 1 0 0\n\n\
+             generation 2\n1 0 0 0 1\n\n\
+             generation 3\n1 1 0 1 1\n\n"
+        );
+        assert_eq!(automaton.generation(), 3);
+
+        assert!(run(&mut automaton, 3, Some(0), &path).is_err());
+        let mut schema = automaton.to_schema().unwrap();
+        schema.generation = u32::MAX - 1;
+        let mut automaton = Automaton::from_schema(&schema).unwrap();
+        assert!(run(&mut automaton, 2, None, &path).is_err());
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn shift_should_move_every_coordinate() {
+        let coordinates = vec![vec![0, 1], vec![2, 0]];
+        assert_eq!(shift(coordinates.clone(), &[]).unwrap(), coordinates);
+        assert_eq!(
+            shift(coordinates.clone(), &[1, 2]).unwrap(),
+            vec![vec![1, 3], vec![3, 2]]
+        );
+        assert!(shift(coordinates, &[1]).is_err());
+    }
 }
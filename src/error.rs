@@ -0,0 +1,107 @@
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+/// errors building or running an automaton
+#[derive(Debug)]
+pub enum Error {
+    /// dimensions differ from those of the grid
+    DimensionMismatch { expected: usize, found: usize },
+    /// a point is outside of a grid of the given dimensions
+    OutOfBounds { point: Vec<usize>, dims: Vec<usize> },
+    /// an automaton was built without any rules
+    EmptyRules,
+    /// a file could not be read or written
+    Io { path: PathBuf, source: io::Error },
+    /// a config file does not hold its schema
+    Parse {
+        path: PathBuf,
+        line: usize,
+        column: usize,
+        cause: String,
+    },
+    /// a sum rule has a type other than 0, 1 or 2
+    InvalidRuleType(u32),
+    /// the grid cannot hold a value or use a boundary
+    Grid(String),
+    /// a neighborhood shape does not fit the grid
+    Neighborhood(String),
+    /// rules are invalid or do not fit the neighborhood
+    Rules(String),
+    /// a rule map is invalid or does not fit its rules
+    RuleMap(String),
+    /// a block rule is invalid or does not fit the grid
+    BlockRule(String),
+    /// a schedule does not fit the grid
+    Schedule(String),
+    /// a second-order automaton can only update synchronously
+    SecondOrderSchedule,
+    /// the builder was given settings that do not fit together
+    Build(String),
+    /// an automaton cannot take a step
+    Automaton(String),
+    /// a generator cannot be made from an automaton
+    Prg(String),
+    /// a pattern cannot be read, placed or written
+    Pattern(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::DimensionMismatch { expected, found } => write!(
+                f,
+                "dimension mismatch! expected {} dimensions, got {}",
+                expected, found
+            ),
+            Error::OutOfBounds { point, dims } => write!(
+                f,
+                "out of bounds! point {:?} is outside of {:?}",
+                point, dims
+            ),
+            Error::EmptyRules => write!(f, "build failed! cannot build an automaton with no rules"),
+            Error::Io { path, source } => {
+                write!(f, "cannot access {}! {}", path.display(), source)
+            }
+            Error::Parse {
+                path,
+                line,
+                column,
+                cause,
+            } => write!(
+                f,
+                "parse error! {}:{}:{}: {}",
+                path.display(),
+                line,
+                column,
+                cause
+            ),
+            Error::InvalidRuleType(rule_type) => {
+                write!(f, "rules error! unsupported rule type {}", rule_type)
+            }
+            Error::Grid(cause) => write!(f, "grid error! {}", cause),
+            Error::Neighborhood(cause) => write!(f, "neighborhood error! {}", cause),
+            Error::Rules(cause) => write!(f, "rules error! {}", cause),
+            Error::RuleMap(cause) => write!(f, "rule map error! {}", cause),
+            Error::BlockRule(cause) => write!(f, "block rule error! {}", cause),
+            Error::Schedule(cause) => write!(f, "schedule error! {}", cause),
+            Error::SecondOrderSchedule => write!(
+                f,
+                "schedule error! a second-order automaton can only update synchronously"
+            ),
+            Error::Build(cause) => write!(f, "build failed! {}", cause),
+            Error::Automaton(cause) => write!(f, "automaton error! {}", cause),
+            Error::Prg(cause) => write!(f, "prg error! {}", cause),
+            Error::Pattern(cause) => write!(f, "pattern error! {}", cause),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
@@ -1,3 +1,5 @@
 pub mod automaton;
 pub mod cli;
+pub mod error;
+pub mod prg;
 pub mod utils;
@@ -4,16 +4,19 @@ use exitfailure::ExitFailure;
 use std::io::{self, Write};
 
 fn main() -> Result<(), ExitFailure> {
-    let mut automaton = cli()?;
+    let mut automaton = match cli()? {
+        Some(automaton) => automaton,
+        None => return Ok(()),
+    };
 
     loop {
-        println!("");
+        println!();
         println!("{}\n", automaton);
         println!("Select an option:");
         println!("1: advance single generation");
         println!("2: advance many generations");
         println!("3: exit");
-        println!("");
+        println!();
         print!("selection: ");
         io::stdout().flush().unwrap();
 
@@ -0,0 +1,197 @@
+use crate::automaton::automaton_builder::AutomatonBuilder;
+use crate::automaton::Automaton;
+use crate::error::Error;
+use rand_core::{impls, RngCore, SeedableRng};
+use std::collections::VecDeque;
+
+/// width of the ring used by from_seed: 256 key bits and a constant byte
+const DEFAULT_WIDTH: usize = 264;
+
+/// generations run after seeding, before any output is produced
+const DEFAULT_WARMUP: u32 = DEFAULT_WIDTH as u32;
+
+/// pseudo-random generator reading tap cells of a cellular automaton
+pub struct CaPrg {
+    automaton: Automaton,
+    taps: Vec<Vec<usize>>,
+    warmup: u32,
+    bits: VecDeque<u8>,
+}
+
+impl CaPrg {
+    /// wrap an automaton, reading output from the given tap cells
+    pub fn new(automaton: Automaton, taps: Vec<Vec<usize>>) -> Result<Self, Error> {
+        if taps.is_empty() {
+            return Err(Error::Prg("at least one tap cell is required".to_string()));
+        }
+        for tap in &taps {
+            automaton.grid().get_point_value(&tap[..])?;
+        }
+        Ok(Self {
+            automaton,
+            taps,
+            warmup: 0,
+            bits: VecDeque::new(),
+        })
+    }
+
+    /// set the number of generations to run after seeding
+    pub fn set_warmup(&mut self, generations: u32) -> &mut Self {
+        self.warmup = generations;
+        self
+    }
+
+    /// reset the grid from a key and run the warmup generations
+    pub fn seed(&mut self, key: &[u8]) -> Result<(), Error> {
+        let cells: Vec<Vec<usize>> = self.automaton.grid().iter().map(|(idx, _)| idx).collect();
+        let mut values = vec![0u32; cells.len()];
+        for (i, byte) in key.iter().enumerate() {
+            for bit in 0..8 {
+                values[(i * 8 + bit) % cells.len()] ^= u32::from((byte >> bit) & 1);
+            }
+        }
+        for (cell, value) in cells.iter().zip(values) {
+            self.automaton.set_point_value(&cell[..], value)?;
+        }
+        self.bits.clear();
+        self.automaton.advance_multi(self.warmup)?;
+        Ok(())
+    }
+
+    /// next bit of the output stream
+    pub fn next_bit(&mut self) -> Result<u8, Error> {
+        while self.bits.is_empty() {
+            self.step()?;
+        }
+        Ok(self.bits.pop_front().unwrap())
+    }
+
+    /// next byte of the output stream, least significant bit first
+    pub fn next_byte(&mut self) -> Result<u8, Error> {
+        let mut byte = 0;
+        for bit in 0..8 {
+            byte |= self.next_bit()? << bit;
+        }
+        Ok(byte)
+    }
+
+    pub fn automaton(&self) -> &Automaton {
+        &self.automaton
+    }
+
+    fn step(&mut self) -> Result<(), Error> {
+        self.automaton.advance()?;
+        for tap in &self.taps {
+            let value = self.automaton.grid().get_point_value(&tap[..])?;
+            self.bits.push_back((value & 1) as u8);
+        }
+        Ok(())
+    }
+}
+
+impl RngCore for CaPrg {
+    fn next_u32(&mut self) -> u32 {
+        impls::next_u32_via_fill(self)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        impls::next_u64_via_fill(self)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        if let Err(e) = self.try_fill_bytes(dest) {
+            panic!("automaton failed to advance: {}", e);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+        for byte in dest.iter_mut() {
+            *byte = self.next_byte().map_err(rand_core::Error::new)?;
+        }
+        Ok(())
+    }
+}
+
+impl SeedableRng for CaPrg {
+    type Seed = [u8; 32];
+
+    /// rule 30 on a ring of 264 cells, tapping the center cell
+    fn from_seed(seed: Self::Seed) -> Self {
+        let automaton = AutomatonBuilder::new(vec![DEFAULT_WIDTH])
+            .set_elementary_rule(30)
+            .build()
+            .expect("default automaton is valid");
+        let mut prg = CaPrg::new(automaton, vec![vec![DEFAULT_WIDTH / 2]])
+            .expect("default tap is inside the grid");
+        prg.set_warmup(DEFAULT_WARMUP);
+        let mut key = seed.to_vec();
+        key.push(1);
+        prg.seed(&key).expect("default automaton is valid");
+        prg
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn single_cell_rule30(width: usize) -> CaPrg {
+        let automaton = AutomatonBuilder::new(vec![width])
+            .set_point(&[width / 2])
+            .unwrap()
+            .set_elementary_rule(30)
+            .build()
+            .unwrap();
+        CaPrg::new(automaton, vec![vec![width / 2]]).unwrap()
+    }
+
+    #[test]
+    fn should_output_rule30_center_column() {
+        let mut prg = single_cell_rule30(65);
+        let expected = [1, 0, 1, 1, 1, 0, 0, 1, 1, 0, 0, 0, 1, 0, 1, 1, 0, 0, 1];
+        for bit in expected.iter() {
+            assert_eq!(prg.next_bit().unwrap(), *bit);
+        }
+    }
+
+    #[test]
+    fn should_pack_bits_lsb_first() {
+        let mut prg = single_cell_rule30(65);
+        assert_eq!(prg.next_byte().unwrap(), 0b1001_1101);
+    }
+
+    #[test]
+    fn should_be_reproducible_from_seed() {
+        let mut a = CaPrg::from_seed([7; 32]);
+        let mut b = CaPrg::from_seed([7; 32]);
+        assert_eq!(a.next_u64(), b.next_u64());
+    }
+
+    #[test]
+    fn should_differ_between_seeds() {
+        let mut a = CaPrg::from_seed([7; 32]);
+        let mut b = CaPrg::from_seed([8; 32]);
+        let mut out_a = [0u8; 16];
+        let mut out_b = [0u8; 16];
+        a.fill_bytes(&mut out_a);
+        b.fill_bytes(&mut out_b);
+        assert_ne!(out_a, out_b);
+    }
+
+    #[test]
+    fn should_not_stall_on_zero_seed() {
+        let mut prg = CaPrg::from_seed([0; 32]);
+        let mut out = [0u8; 16];
+        prg.fill_bytes(&mut out);
+        assert_ne!(out, [0u8; 16]);
+    }
+
+    #[test]
+    fn should_reject_tap_outside_grid() {
+        let automaton = AutomatonBuilder::new(vec![8])
+            .set_elementary_rule(30)
+            .build()
+            .unwrap();
+        assert!(CaPrg::new(automaton, vec![vec![8]]).is_err());
+    }
+}
@@ -5,14 +5,14 @@ pub struct CoordinatesIterator {
 }
 
 impl CoordinatesIterator {
-    pub fn new(modulos: &Vec<usize>) -> Self {
-        let mut state = modulos.clone();
+    pub fn new(modulos: &[usize]) -> Self {
+        let mut state = modulos.to_vec();
         for i in &mut state {
             *i -= 1;
         }
         Self {
-            modulos: modulos.clone(),
-            state: state,
+            modulos: modulos.to_vec(),
+            state,
             finished: false,
         }
     }
@@ -28,7 +28,7 @@ impl CoordinatesIterator {
                 carry = true;
                 continue;
             }
-            self.state[i] = self.state[i] - 1;
+            self.state[i] -= 1;
             carry = false;
         }
     }
@@ -1,20 +1,63 @@
 pub mod coordinates_iterator;
+pub mod split_mix;
 
 use std::str::FromStr;
 
 //transforms a string representation of a vector to the actual vector
-pub fn string_to_vector<T>(string: &String) -> Vec<T>
+pub fn string_to_vector<T>(string: &str) -> Vec<T>
 where
     T: FromStr,
     T::Err: std::fmt::Debug,
 {
-    let res: Vec<T> = (&string[1..string.len() - 1])
+    let res: Vec<T> = string[1..string.len() - 1]
         .split(",")
         .map(|s| s.to_string().parse::<T>().unwrap())
         .collect();
     res
 }
 
+/// step a coordinate to the next cell in row-major order
+pub fn next_coordinate(coordinate: &mut [usize], dims: &[usize]) {
+    for axis in (0..coordinate.len()).rev() {
+        coordinate[axis] += 1;
+        if coordinate[axis] < dims[axis] {
+            return;
+        }
+        coordinate[axis] = 0;
+    }
+}
+
+/// coordinate of the cell at a linear index in row-major order
+pub fn coordinate_of(index: usize, dims: &[usize]) -> Vec<usize> {
+    let mut coordinate = vec![0; dims.len()];
+    set_coordinate_of(&mut coordinate, index, dims);
+    coordinate
+}
+
+/// set a coordinate to that of a linear index
+pub fn set_coordinate_of(coordinate: &mut [usize], index: usize, dims: &[usize]) {
+    let mut rest = index;
+    for axis in (0..dims.len()).rev() {
+        coordinate[axis] = rest % dims[axis];
+        rest /= dims[axis];
+    }
+}
+
+/// linear index of a coordinate, if inside dims
+pub fn index_of(coordinate: &[usize], dims: &[usize]) -> Option<usize> {
+    if coordinate.len() != dims.len() {
+        return None;
+    }
+    let mut index = 0;
+    for (coordinate, dim) in coordinate.iter().zip(dims) {
+        if coordinate >= dim {
+            return None;
+        }
+        index = index * dim + coordinate;
+    }
+    Some(index)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -23,4 +66,18 @@ mod tests {
     fn test() {
         string_to_vector::<usize>(&String::from("(1,2,3)"));
     }
+
+    #[test]
+    fn next_coordinate_should_carry() {
+        let mut coordinate = vec![0, 2];
+        next_coordinate(&mut coordinate, &[2, 3]);
+        assert_eq!(coordinate, vec![1, 0]);
+        next_coordinate(&mut coordinate, &[2, 3]);
+        assert_eq!(coordinate, vec![1, 1]);
+        assert_eq!(coordinate_of(4, &[2, 3]), coordinate);
+        assert_eq!(index_of(&coordinate, &[2, 3]), Some(4));
+        assert_eq!(index_of(&[2, 0], &[2, 3]), None);
+        set_coordinate_of(&mut coordinate, 2, &[2, 3]);
+        assert_eq!(coordinate, vec![0, 2]);
+    }
 }